@@ -403,6 +403,11 @@ pub struct Message {
     /// using `JetStream`.
     #[doc(hidden)]
     pub double_acked: Arc<AtomicBool>,
+
+    /// Whether a final acknowledgement (ack, nak or term) has been sent for
+    /// this message using `JetStream`.
+    #[doc(hidden)]
+    pub acknowledged: Arc<AtomicBool>,
 }
 
 impl From<crate::Message> for Message {
@@ -414,6 +419,7 @@ impl From<crate::Message> for Message {
             headers: sync.headers,
             client: sync.client,
             double_acked: sync.double_acked,
+            acknowledged: sync.acknowledged,
         }
     }
 }
@@ -469,6 +475,7 @@ impl Default for Message {
             headers: None,
             client: None,
             double_acked: Arc::new(AtomicBool::new(false)),
+            acknowledged: Arc::new(AtomicBool::new(false)),
         }
     }
 }
@@ -16,6 +16,7 @@ use std::fmt;
 use std::io::prelude::*;
 use std::io::{self, BufReader, BufWriter, Error, ErrorKind};
 use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use std::thread::{self, JoinHandle};
@@ -42,6 +43,9 @@ pub(crate) struct State {
     write: Mutex<WriteState>,
     pub(crate) read: Mutex<ReadState>,
     meta: Mutex<MetaState>,
+
+    /// Number of times the client reconnected to a server.
+    pub(crate) reconnects: AtomicU64,
 }
 
 struct MetaState {
@@ -146,6 +150,7 @@ impl Client {
                     last_active: Instant::now(),
                     pings_out: 0,
                 }),
+                reconnects: AtomicU64::new(0),
             }),
             server_info: Arc::new(Mutex::new(ServerInfo::default())),
             shutdown: Arc::new(Mutex::new(false)),
@@ -715,6 +720,7 @@ impl Client {
             if self.reconnect(server_info, writer).is_ok() {
                 // Connected! Now dispatch MSG operations.
                 if !first_connect {
+                    self.state.reconnects.fetch_add(1, Ordering::Relaxed);
                     connector.get_options().reconnect_callback.call();
                 }
                 if self.dispatch(reader, &mut connector).is_ok() {
@@ -898,6 +904,7 @@ impl Client {
                             headers: None,
                             client: Some(self.clone()),
                             double_acked: Default::default(),
+                            acknowledged: Default::default(),
                         };
 
                         // Preprocess and drop the message from the buffer if it the predicate
@@ -955,6 +962,7 @@ impl Client {
                             headers: Some(headers),
                             client: Some(self.clone()),
                             double_acked: Default::default(),
+                            acknowledged: Default::default(),
                         };
 
                         // Preprocess and drop the message from the buffer if it the predicate
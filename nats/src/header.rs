@@ -53,6 +53,24 @@ pub const NATS_LAST_CONSUMER: &str = "Nats-Last-Consumer";
 /// Nats-Consumer-Stalled
 pub const NATS_CONSUMER_STALLED: &str = "Nats-Consumer-Stalled";
 
+/// Nats-Stream
+pub const NATS_STREAM: &str = "Nats-Stream";
+
+/// Nats-Subject
+pub const NATS_SUBJECT: &str = "Nats-Subject";
+
+/// Nats-Sequence
+pub const NATS_SEQUENCE: &str = "Nats-Sequence";
+
+/// Nats-Time-Stamp
+pub const NATS_TIME_STAMP: &str = "Nats-Time-Stamp";
+
+/// Nats-Pending-Messages
+pub const NATS_PENDING_MESSAGES: &str = "Nats-Pending-Messages";
+
+/// Nats-Pending-Bytes
+pub const NATS_PENDING_BYTES: &str = "Nats-Pending-Bytes";
+
 /// A multi-map from header name to a set of values for that header
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct HeaderMap {
@@ -0,0 +1,377 @@
+// Copyright 2020-2022 The NATS Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use time::serde::rfc3339;
+
+use crate::jetstream::{DateTime, PeerInfo};
+use crate::{Message, Subscription};
+
+/// Subject on which the server publishes `JetStream` advisories.
+pub const ADVISORY_SUBJECT: &str = "$JS.EVENT.ADVISORY.>";
+
+/// Subject on which the server publishes `JetStream` metrics.
+pub const METRIC_SUBJECT: &str = "$JS.EVENT.METRIC.>";
+
+const MAX_DELIVER_TYPE: &str = "io.nats.jetstream.advisory.v1.max_deliver";
+const TERMINATED_TYPE: &str = "io.nats.jetstream.advisory.v1.terminated";
+const NAK_TYPE: &str = "io.nats.jetstream.advisory.v1.nak";
+const STREAM_ACTION_TYPE: &str = "io.nats.jetstream.advisory.v1.stream_action";
+const CONSUMER_ACTION_TYPE: &str = "io.nats.jetstream.advisory.v1.consumer_action";
+const STREAM_LEADER_ELECTED_TYPE: &str = "io.nats.jetstream.advisory.v1.stream_leader_elected";
+const CONSUMER_LEADER_ELECTED_TYPE: &str = "io.nats.jetstream.advisory.v1.consumer_leader_elected";
+const STREAM_QUORUM_LOST_TYPE: &str = "io.nats.jetstream.advisory.v1.stream_quorum_lost";
+const CONSUMER_QUORUM_LOST_TYPE: &str = "io.nats.jetstream.advisory.v1.consumer_quorum_lost";
+const CONSUMER_ACK_TYPE: &str = "io.nats.jetstream.metric.v1.consumer_ack";
+
+/// Sent when a message was delivered the maximum number of times allowed by the consumer
+/// without being acknowledged.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MaxDeliveriesAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream the message belongs to
+    pub stream: String,
+    /// The consumer which exceeded its deliveries
+    pub consumer: String,
+    /// The stream sequence of the message
+    pub stream_seq: u64,
+    /// How many times the message was delivered
+    pub deliveries: u64,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Sent when a message was terminated with `AckKind::Term`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MessageTerminatedAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream the message belongs to
+    pub stream: String,
+    /// The consumer the message was terminated on
+    pub consumer: String,
+    /// The consumer sequence of the message
+    pub consumer_seq: u64,
+    /// The stream sequence of the message
+    pub stream_seq: u64,
+    /// How many times the message was delivered
+    pub deliveries: u64,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Sent when a message was negatively acknowledged.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MessageNakedAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream the message belongs to
+    pub stream: String,
+    /// The consumer the message was naked on
+    pub consumer: String,
+    /// The consumer sequence of the message
+    pub consumer_seq: u64,
+    /// The stream sequence of the message
+    pub stream_seq: u64,
+    /// How many times the message was delivered
+    pub deliveries: u64,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// The action reported by stream and consumer action advisories.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    /// The stream or consumer was created
+    #[serde(rename = "create")]
+    Create,
+    /// The stream or consumer was deleted
+    #[serde(rename = "delete")]
+    Delete,
+    /// The stream or consumer was updated
+    #[serde(rename = "modify")]
+    Modify,
+}
+
+/// Sent when a stream was created, updated or deleted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct StreamActionAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream the action applies to
+    pub stream: String,
+    /// What happened to the stream
+    pub action: ActionKind,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Sent when a consumer was created or deleted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConsumerActionAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream of the consumer
+    pub stream: String,
+    /// The consumer the action applies to
+    pub consumer: String,
+    /// What happened to the consumer
+    pub action: ActionKind,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Sent when a clustered stream elected a new leader.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct StreamLeaderElectedAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream which elected a leader
+    pub stream: String,
+    /// The server name of the new leader
+    pub leader: String,
+    /// The other members of the raft group
+    #[serde(default)]
+    pub replicas: Vec<PeerInfo>,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Sent when a clustered consumer elected a new leader.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConsumerLeaderElectedAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream of the consumer
+    pub stream: String,
+    /// The consumer which elected a leader
+    pub consumer: String,
+    /// The server name of the new leader
+    pub leader: String,
+    /// The other members of the raft group
+    #[serde(default)]
+    pub replicas: Vec<PeerInfo>,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Sent when a clustered stream lost the quorum of its raft group.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct StreamQuorumLostAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream which lost its quorum
+    pub stream: String,
+    /// The members of the raft group
+    #[serde(default)]
+    pub replicas: Vec<PeerInfo>,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Sent when a clustered consumer lost the quorum of its raft group.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConsumerQuorumLostAdvisory {
+    /// Unique id of the advisory
+    pub id: String,
+    /// The time the advisory was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream of the consumer
+    pub stream: String,
+    /// The consumer which lost its quorum
+    pub consumer: String,
+    /// The members of the raft group
+    #[serde(default)]
+    pub replicas: Vec<PeerInfo>,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Sampled acknowledgement metric, sent for consumers with a `sample_frequency`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConsumerAckMetric {
+    /// Unique id of the metric
+    pub id: String,
+    /// The time the metric was created
+    #[serde(with = "rfc3339")]
+    pub timestamp: DateTime,
+    /// The stream the message belongs to
+    pub stream: String,
+    /// The consumer the message was acknowledged on
+    pub consumer: String,
+    /// The consumer sequence of the message
+    pub consumer_seq: u64,
+    /// The stream sequence of the message
+    pub stream_seq: u64,
+    /// How long it took to acknowledge the message
+    #[serde(with = "serde_nanos")]
+    pub ack_time: Duration,
+    /// How many times the message was delivered
+    pub deliveries: u64,
+    /// The `JetStream` domain, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// An advisory or metric event published by the `JetStream` server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Advisory {
+    /// A message exceeded the maximum number of deliveries
+    MaxDeliveries(MaxDeliveriesAdvisory),
+    /// A message was terminated
+    MessageTerminated(MessageTerminatedAdvisory),
+    /// A message was negatively acknowledged
+    MessageNaked(MessageNakedAdvisory),
+    /// A stream was created, updated or deleted
+    StreamAction(StreamActionAdvisory),
+    /// A consumer was created or deleted
+    ConsumerAction(ConsumerActionAdvisory),
+    /// A stream elected a new leader
+    StreamLeaderElected(StreamLeaderElectedAdvisory),
+    /// A consumer elected a new leader
+    ConsumerLeaderElected(ConsumerLeaderElectedAdvisory),
+    /// A stream lost its quorum
+    StreamQuorumLost(StreamQuorumLostAdvisory),
+    /// A consumer lost its quorum
+    ConsumerQuorumLost(ConsumerQuorumLostAdvisory),
+    /// A sampled acknowledgement metric
+    ConsumerAck(ConsumerAckMetric),
+    /// An event of a schema this client does not know about
+    Other {
+        /// The schema type of the event
+        kind: String,
+        /// The raw JSON of the event
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Deserialize)]
+struct AdvisoryKind {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl Advisory {
+    /// Parses the JSON payload of an advisory or metric event.
+    pub fn from_slice(data: &[u8]) -> io::Result<Advisory> {
+        let AdvisoryKind { kind } = serde_json::from_slice(data)?;
+
+        let advisory = match kind.as_str() {
+            MAX_DELIVER_TYPE => Advisory::MaxDeliveries(serde_json::from_slice(data)?),
+            TERMINATED_TYPE => Advisory::MessageTerminated(serde_json::from_slice(data)?),
+            NAK_TYPE => Advisory::MessageNaked(serde_json::from_slice(data)?),
+            STREAM_ACTION_TYPE => Advisory::StreamAction(serde_json::from_slice(data)?),
+            CONSUMER_ACTION_TYPE => Advisory::ConsumerAction(serde_json::from_slice(data)?),
+            STREAM_LEADER_ELECTED_TYPE => {
+                Advisory::StreamLeaderElected(serde_json::from_slice(data)?)
+            }
+            CONSUMER_LEADER_ELECTED_TYPE => {
+                Advisory::ConsumerLeaderElected(serde_json::from_slice(data)?)
+            }
+            STREAM_QUORUM_LOST_TYPE => Advisory::StreamQuorumLost(serde_json::from_slice(data)?),
+            CONSUMER_QUORUM_LOST_TYPE => {
+                Advisory::ConsumerQuorumLost(serde_json::from_slice(data)?)
+            }
+            CONSUMER_ACK_TYPE => Advisory::ConsumerAck(serde_json::from_slice(data)?),
+            _ => Advisory::Other {
+                kind,
+                data: data.to_vec(),
+            },
+        };
+
+        Ok(advisory)
+    }
+}
+
+/// A subscription to `JetStream` advisories or metrics, created with `JetStream::advisories` or
+/// `JetStream::metrics`.
+#[derive(Debug)]
+pub struct Advisories {
+    subscription: Subscription,
+}
+
+impl Advisories {
+    pub(crate) fn new(subscription: Subscription) -> Advisories {
+        Advisories { subscription }
+    }
+
+    /// Gets the next event, or `None` if the subscription has been unsubscribed or the connection
+    /// closed.
+    pub fn next(&self) -> Option<io::Result<Advisory>> {
+        self.subscription.next().map(|message| parse(&message))
+    }
+
+    /// Gets the next event if one is available.
+    pub fn try_next(&self) -> Option<io::Result<Advisory>> {
+        self.subscription.try_next().map(|message| parse(&message))
+    }
+
+    /// Gets the next event, or a timeout error if none arrived in time.
+    pub fn next_timeout(&self, timeout: Duration) -> io::Result<Advisory> {
+        parse(&self.subscription.next_timeout(timeout)?)
+    }
+
+    /// Unsubscribes from the events.
+    pub fn unsubscribe(self) -> io::Result<()> {
+        self.subscription.unsubscribe()
+    }
+}
+
+impl Iterator for Advisories {
+    type Item = io::Result<Advisory>;
+    fn next(&mut self) -> Option<Self::Item> {
+        Advisories::next(self)
+    }
+}
+
+fn parse(message: &Message) -> io::Result<Advisory> {
+    Advisory::from_slice(&message.data)
+}
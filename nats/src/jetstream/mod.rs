@@ -100,31 +100,56 @@ use std::{
     error, fmt,
     fmt::Debug,
     io::{self, ErrorKind},
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
 
+use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::thread;
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 const ORDERED_IDLE_HEARTBEAT: Duration = Duration::from_nanos(5_000_000_000);
 
+const DEFAULT_PUBLISH_ASYNC_MAX_PENDING: usize = 4000;
+
+const DEFAULT_PUBLISH_ASYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SNAPSHOT_CHUNK_SIZE: usize = 128 * 1024;
+
+const SNAPSHOT_CHUNK_TIMEOUT: Duration = Duration::from_secs(10);
+
+const SNAPSHOT_RESTORE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Bounds how long administrative requests wait for a raft group to elect a leader.
+const LEADER_ELECTION_TIMEOUT: Duration = Duration::from_secs(10);
+const LEADER_ELECTION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 /// Pull subscriptions
 pub mod pull_subscription;
 
 /// Push subscriptions
 pub mod push_subscription;
 
+mod advisory;
+mod progress_guard;
+mod publish_async;
 mod types;
 
 // We use a fully qualified crate path so these are documented as re-exports.
-pub use crate::jetstream::pull_subscription::PullSubscription;
+pub use crate::jetstream::pull_subscription::{PullStatus, PullSubscription};
 pub use crate::jetstream::push_subscription::PushSubscription;
 
+use push_subscription::OrderedConsumer;
+
+pub use progress_guard::ProgressGuard;
+pub use publish_async::PendingPublishAck;
+use publish_async::PublishAsync;
+
+pub use advisory::*;
 pub use types::*;
 
 #[deprecated(note = "Use PullSubscribeOptions instead")]
@@ -140,12 +165,19 @@ use crate::{
 #[derive(Clone)]
 pub struct JetStreamOptions {
     pub(crate) api_prefix: String,
+    pub(crate) publish_async_max_pending: usize,
+    pub(crate) publish_async_timeout: Duration,
 }
 
 impl Debug for JetStreamOptions {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         f.debug_map()
             .entry(&"api_prefix", &self.api_prefix)
+            .entry(
+                &"publish_async_max_pending",
+                &self.publish_async_max_pending,
+            )
+            .entry(&"publish_async_timeout", &self.publish_async_timeout)
             .finish()
     }
 }
@@ -154,6 +186,8 @@ impl Default for JetStreamOptions {
     fn default() -> JetStreamOptions {
         JetStreamOptions {
             api_prefix: "$JS.API.".to_string(),
+            publish_async_max_pending: DEFAULT_PUBLISH_ASYNC_MAX_PENDING,
+            publish_async_timeout: DEFAULT_PUBLISH_ASYNC_TIMEOUT,
         }
     }
 }
@@ -202,6 +236,34 @@ impl JetStreamOptions {
             self.api_prefix(format!("$JS.{}.API", domain))
         }
     }
+
+    /// Set the maximum number of asynchronously published messages which may be waiting for
+    /// an acknowledgement at the same time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let options = nats::JetStreamOptions::new()
+    ///   .publish_async_max_pending(256);
+    /// ```
+    pub fn publish_async_max_pending(mut self, max_pending: usize) -> Self {
+        self.publish_async_max_pending = max_pending;
+        self
+    }
+
+    /// Set how long an asynchronously published message may wait for its acknowledgement
+    /// before it fails with a timeout error and leaves the pending window.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let options = nats::JetStreamOptions::new()
+    ///   .publish_async_timeout(std::time::Duration::from_secs(10));
+    /// ```
+    pub fn publish_async_timeout(mut self, timeout: Duration) -> Self {
+        self.publish_async_timeout = timeout;
+        self
+    }
 }
 
 /// `ApiResponse` is a standard response from the `JetStream` JSON Api
@@ -437,26 +499,130 @@ pub enum ErrorCode {
     ConsumerDescriptionTooLong = 10107,
     /// Header size exceeds maximum allowed of 64k
     StreamHeaderExceedsMaximum = 10097,
+    /// An error code which is not known to this client
+    Unknown = 0,
+}
+
+impl ErrorCode {
+    /// Converts a raw error code, falling back to `ErrorCode::Unknown` for codes added by newer
+    /// servers.
+    fn from_raw(code: u64) -> ErrorCode {
+        let deserializer: serde::de::value::U64Deserializer<serde::de::value::Error> =
+            serde::de::IntoDeserializer::into_deserializer(code);
+
+        ErrorCode::deserialize(deserializer).unwrap_or(ErrorCode::Unknown)
+    }
 }
 
 /// `Error` type returned from an API response when an error occurs.
+///
+/// Every `JetStream` method returns these wrapped in an `io::Error` of kind
+/// `io::ErrorKind::Other`. Use `Error::from_io_error` to recover them.
+///
+/// # Example
+///
+/// ```no_run
+/// # use nats::jetstream::Error;
+/// # fn main() -> std::io::Result<()> {
+/// # let client = nats::connect("demo.nats.io")?;
+/// # let context = nats::jetstream::new(client);
+/// match context.stream_info("missing") {
+///     Err(err) if Error::from_io_error(&err).map_or(false, Error::is_stream_not_found) => {
+///         println!("stream does not exist");
+///     }
+///     result => println!("{:?}", result?),
+/// }
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Error {
     code: usize,
-    err_code: ErrorCode,
+    #[serde(default)]
+    err_code: u64,
     description: Option<String>,
 }
 
 impl Error {
+    pub(crate) fn new(code: usize, error_code: ErrorCode, description: Option<String>) -> Error {
+        Error {
+            code,
+            err_code: error_code as u64,
+            description,
+        }
+    }
+
+    /// Returns the `JetStream` API error wrapped by the given `io::Error`, if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&Error> {
+        err.get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+    }
+
     /// Returns the status code assosciated with this error
     pub fn code(&self) -> usize {
         self.code
     }
 
     /// Returns the server side error code associated with this error.
+    ///
+    /// Codes which are not known to this client are returned as `ErrorCode::Unknown`, use
+    /// `raw_error_code` to inspect them.
     pub fn error_code(&self) -> ErrorCode {
+        ErrorCode::from_raw(self.err_code)
+    }
+
+    /// Returns the server side error code as sent by the server.
+    pub fn raw_error_code(&self) -> u64 {
         self.err_code
     }
+
+    /// Returns the description of this error, if any.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Returns true if the error indicates the stream does not exist.
+    pub fn is_stream_not_found(&self) -> bool {
+        self.error_code() == ErrorCode::StreamNotFound
+    }
+
+    /// Returns true if the error indicates the consumer does not exist.
+    pub fn is_consumer_not_found(&self) -> bool {
+        self.error_code() == ErrorCode::ConsumerNotFound
+    }
+
+    /// Returns true if the error indicates the requested message does not exist.
+    pub fn is_no_message_found(&self) -> bool {
+        self.error_code() == ErrorCode::NoMessageFound
+    }
+
+    /// Returns true if the error indicates an expected last sequence did not match.
+    pub fn is_wrong_last_sequence(&self) -> bool {
+        self.error_code() == ErrorCode::StreamWrongLastSequence
+    }
+
+    /// Returns true if the error indicates an expected last message id did not match.
+    pub fn is_wrong_last_msg_id(&self) -> bool {
+        self.error_code() == ErrorCode::StreamWrongLastMsgId
+    }
+
+    /// Returns true if the error indicates a stream with the same name already exists.
+    pub fn is_stream_name_exist(&self) -> bool {
+        self.error_code() == ErrorCode::StreamNameExist
+    }
+
+    /// Returns true if the error indicates a consumer with the same name already exists.
+    pub fn is_consumer_name_exist(&self) -> bool {
+        self.error_code() == ErrorCode::ConsumerNameExist
+    }
+
+    /// Returns true if the error indicates `JetStream` is not enabled on the server or account.
+    pub fn is_not_enabled(&self) -> bool {
+        matches!(
+            self.error_code(),
+            ErrorCode::NotEnabled | ErrorCode::NotEnabledForAccount
+        )
+    }
 }
 
 impl fmt::Display for Error {
@@ -464,9 +630,9 @@ impl fmt::Display for Error {
         write!(
             fmt,
             "{} (code {}, error code {})",
+            self.description.as_deref().unwrap_or("unknown"),
             self.code,
-            self.description.as_ref().unwrap_or(&"unknown".to_string()),
-            self.err_code as u64,
+            self.err_code,
         )
     }
 }
@@ -476,6 +642,8 @@ impl error::Error for Error {}
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 struct PagedRequest {
     offset: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subject: Option<String>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -496,6 +664,7 @@ struct PagedResponse<T> {
 pub struct PagedIterator<'a, T> {
     manager: &'a JetStream,
     subject: String,
+    filter: Option<String>,
     offset: i64,
     items: VecDeque<T>,
     done: bool,
@@ -518,6 +687,7 @@ where
         }
         let req = serde_json::ser::to_vec(&PagedRequest {
             offset: self.offset,
+            subject: self.filter.clone(),
         })
         .unwrap();
 
@@ -555,6 +725,7 @@ where
 pub struct JetStream {
     pub(crate) connection: Connection,
     pub(crate) options: JetStreamOptions,
+    publish_async: Arc<OnceCell<PublishAsync>>,
 }
 
 impl JetStream {
@@ -563,6 +734,7 @@ impl JetStream {
         Self {
             connection,
             options,
+            publish_async: Arc::new(OnceCell::new()),
         }
     }
 
@@ -613,54 +785,139 @@ impl JetStream {
         maybe_headers: Option<&HeaderMap>,
         msg: impl AsRef<[u8]>,
     ) -> io::Result<PublishAck> {
-        let maybe_headers = if let Some(options) = maybe_options {
-            let mut headers = maybe_headers.map_or_else(HeaderMap::default, HeaderMap::clone);
+        let maybe_headers = headers_with_options(maybe_options, maybe_headers);
+        let maybe_timeout = maybe_options.and_then(|options| options.timeout);
 
-            if let Some(v) = options.id.as_ref() {
-                headers.insert(header::NATS_MSG_ID, v.to_string());
-            }
+        let res_msg = self.connection.request_with_headers_or_timeout(
+            subject,
+            maybe_headers.as_ref(),
+            maybe_timeout,
+            msg,
+        )?;
 
-            if let Some(v) = options.expected_last_msg_id.as_ref() {
-                headers.insert(header::NATS_EXPECTED_LAST_MSG_ID, v.to_string());
-            }
+        parse_publish_ack(&res_msg.data)
+    }
 
-            if let Some(v) = options.expected_stream.as_ref() {
-                headers.insert(header::NATS_EXPECTED_STREAM, v.to_string());
-            }
+    /// Publishes a message to `JetStream` without waiting for the acknowledgement.
+    ///
+    /// The returned handle can be used to wait for the `PublishAck`. The number of messages
+    /// waiting for an acknowledgement is limited by `JetStreamOptions::publish_async_max_pending`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// # context.add_stream("publish_async")?;
+    /// let mut acks = Vec::new();
+    /// for i in 0..100 {
+    ///     acks.push(context.publish_async("publish_async", format!("message {}", i))?);
+    /// }
+    ///
+    /// context.publish_async_complete()?;
+    ///
+    /// for ack in acks {
+    ///     println!("published message {:?}", ack.wait()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn publish_async(
+        &self,
+        subject: &str,
+        data: impl AsRef<[u8]>,
+    ) -> io::Result<PendingPublishAck> {
+        self.publish_async_with_options_or_headers(subject, None, None, data)
+    }
 
-            if let Some(v) = options.expected_last_sequence.as_ref() {
-                headers.insert(header::NATS_EXPECTED_LAST_SEQUENCE, v.to_string());
-            }
+    /// Publishes a message to `JetStream` with the given options without waiting for the
+    /// acknowledgement.
+    ///
+    /// The `timeout` option is ignored, use `PendingPublishAck::wait_timeout` instead.
+    pub fn publish_async_with_options(
+        &self,
+        subject: &str,
+        data: impl AsRef<[u8]>,
+        options: &PublishOptions,
+    ) -> io::Result<PendingPublishAck> {
+        self.publish_async_with_options_or_headers(subject, Some(options), None, data)
+    }
 
-            if let Some(v) = options.expected_last_subject_sequence.as_ref() {
-                headers.insert(header::NATS_EXPECTED_LAST_SUBJECT_SEQUENCE, v.to_string());
-            }
+    /// Publishes a `Message` to `JetStream` without waiting for the acknowledgement.
+    pub fn publish_message_async(&self, message: &Message) -> io::Result<PendingPublishAck> {
+        self.publish_async_with_options_or_headers(
+            &message.subject,
+            None,
+            message.headers.as_ref(),
+            &message.data,
+        )
+    }
 
-            Some(headers)
-        } else {
-            maybe_headers.cloned()
-        };
+    /// Publishes a `Message` to `JetStream` with the given options without waiting for the
+    /// acknowledgement.
+    pub fn publish_message_async_with_options(
+        &self,
+        message: &Message,
+        options: &PublishOptions,
+    ) -> io::Result<PendingPublishAck> {
+        self.publish_async_with_options_or_headers(
+            &message.subject,
+            Some(options),
+            message.headers.as_ref(),
+            &message.data,
+        )
+    }
 
-        let maybe_timeout = maybe_options.and_then(|options| options.timeout);
+    fn publish_async_with_options_or_headers(
+        &self,
+        subject: &str,
+        maybe_options: Option<&PublishOptions>,
+        maybe_headers: Option<&HeaderMap>,
+        msg: impl AsRef<[u8]>,
+    ) -> io::Result<PendingPublishAck> {
+        let publish_async = self.publish_async.get_or_try_init(|| {
+            PublishAsync::new(
+                &self.connection,
+                self.options.publish_async_max_pending,
+                self.options.publish_async_timeout,
+            )
+        })?;
 
-        let res_msg = self.connection.request_with_headers_or_timeout(
+        let maybe_headers = headers_with_options(maybe_options, maybe_headers);
+
+        publish_async.publish(
+            &self.connection,
             subject,
             maybe_headers.as_ref(),
-            maybe_timeout,
-            msg,
-        )?;
+            msg.as_ref(),
+        )
+    }
+
+    /// Returns the number of asynchronously published messages which are still waiting for an
+    /// acknowledgement.
+    pub fn publish_async_pending(&self) -> usize {
+        self.publish_async
+            .get()
+            .map_or(0, |publish_async| publish_async.pending())
+    }
 
-        let res: ApiResponse<PublishAck> = serde_json::de::from_slice(&res_msg.data)?;
-        match res {
-            ApiResponse::Ok(pub_ack) => Ok(pub_ack),
-            ApiResponse::Err { error, .. } => {
-                log::debug!(
-                    "failed to parse API response: {:?}",
-                    std::str::from_utf8(&res_msg.data)
-                );
+    /// Blocks until all asynchronously published messages have been acknowledged, returning a
+    /// timeout error if any of them was not acknowledged within
+    /// `JetStreamOptions::publish_async_timeout`.
+    pub fn publish_async_complete(&self) -> io::Result<()> {
+        match self.publish_async.get() {
+            Some(publish_async) => publish_async.complete(None),
+            None => Ok(()),
+        }
+    }
 
-                Err(io::Error::new(ErrorKind::Other, error))
-            }
+    /// Blocks until all asynchronously published messages have been acknowledged, returning a
+    /// timeout error if that does not happen in time.
+    pub fn publish_async_complete_timeout(&self, timeout: Duration) -> io::Result<()> {
+        match self.publish_async.get() {
+            Some(publish_async) => publish_async.complete(Some(Instant::now() + timeout)),
+            None => Ok(()),
         }
     }
 
@@ -732,9 +989,26 @@ impl JetStream {
         let maybe_durable_consumer =
             maybe_options.and_then(|options| options.durable_name.to_owned());
 
+        // Checks specific to ordered consumers.
+        let is_ordered = maybe_options.map_or(false, |options| options.ordered);
+        if is_ordered {
+            let maybe_config_durable = maybe_options
+                .and_then(|options| options.consumer_config.as_ref())
+                .and_then(|config| config.durable_name.as_ref());
+
+            if maybe_durable_consumer.is_some() || maybe_config_durable.is_some() {
+                return Err(io::Error::new(
+                    ErrorKind::Other,
+                    "durable name can not be set for an ordered consumer",
+                ));
+            }
+        }
+
         let process_consumer_info = |info: ConsumerInfo| {
-            // run the standard validation for pull consumer.
-            info.config.validate_for(&ConsumerKind::Pull)?;
+            // run the standard validation for pull consumer, ordered consumers do not ack.
+            if !is_ordered {
+                info.config.validate_for(&ConsumerKind::Pull)?;
+            }
 
             // check mismatches between user config and info
 
@@ -756,6 +1030,11 @@ impl JetStream {
                 match self.consumer_info(stream_name, durable_name) {
                     Ok(info) => (info, ConsumerOwnership::No),
                     Err(err) => {
+                        // Keep API errors intact so the error code can be inspected.
+                        if Error::from_io_error(&err).is_some() {
+                            return Err(err);
+                        }
+
                         return Err(io::Error::new(
                             ErrorKind::NotFound,
                             format!("provided durable consumer doesn't exist: {}", err),
@@ -764,7 +1043,7 @@ impl JetStream {
                 }
             } else {
                 // create ephemeral consumer
-                let consumer_config = {
+                let mut consumer_config = {
                     maybe_options
                         .and_then(|options| options.consumer_config.clone())
                         .unwrap_or_else(|| ConsumerConfig {
@@ -776,7 +1055,16 @@ impl JetStream {
                             ..Default::default()
                         })
                 };
-                consumer_config.validate_for(&ConsumerKind::Pull)?;
+
+                // If we're ordered, configuration must be a certain way.
+                if is_ordered {
+                    consumer_config.ack_policy = AckPolicy::None;
+                    consumer_config.max_deliver = 1;
+                    consumer_config.mem_storage = true;
+                    consumer_config.num_replicas = 1;
+                } else {
+                    consumer_config.validate_for(&ConsumerKind::Pull)?;
+                }
                 (
                     self.add_consumer(stream_name, consumer_config)?,
                     ConsumerOwnership::Yes,
@@ -794,6 +1082,7 @@ impl JetStream {
             consumer_ownership,
             inbox,
             messages,
+            maybe_options,
             self.clone(),
         ))
     }
@@ -1245,71 +1534,29 @@ impl JetStream {
         // the subscription can be redirected as needed.
         let shared_sid = Arc::new(AtomicU64::new(0));
 
+        // The consumer name is shared as well, as ordered consumers are recreated as needed.
+        let shared_consumer = Arc::new(Mutex::new(String::new()));
+
+        // Ordered consumers are kept in sequence by recreating them whenever a gap is detected.
+        let maybe_ordered = if is_ordered {
+            Some(Arc::new(OrderedConsumer::new(
+                self.clone(),
+                stream_name.clone(),
+                consumer_config.clone(),
+                shared_sid.clone(),
+                shared_consumer.clone(),
+            )))
+        } else {
+            None
+        };
+
         // Create our message preprocessor
         let preprocessor = {
-            // Sequences for gap detection
-            let sequence_pair = Arc::new(Mutex::new(SequencePair {
-                consumer_seq: 0,
-                stream_seq: 0,
-            }));
-
-            let handle_sequence_mismatch = {
-                // Context used to send replies and make requests.
-                let context = self.clone();
-                let consumer_config = consumer_config.clone();
-                let sequence_pair = sequence_pair.clone();
-                let stream_name = stream_name.clone();
-                let shared_sid = shared_sid.clone();
-
-                move |sid: u64, start_seq: u64| {
-                    let stream_name = stream_name.clone();
-                    let context = context.clone();
-                    let consumer_config = consumer_config.clone();
-                    let sequence_pair = sequence_pair.clone();
-                    let shared_sid = shared_sid.clone();
-
-                    // Immediately mute the subscription so that messages no longer get delivered to it.
-                    //
-                    // If we are already muted then the triggering slipped through and we can
-                    // return early as a resubscription will already be on the way.
-                    if !context.connection.0.client.mute(sid).unwrap() {
-                        return true;
-                    }
-
-                    thread::spawn(move || {
-                        let new_deliver_subject = context.connection.new_inbox();
-                        let result = context
-                            .connection
-                            .0
-                            .client
-                            .resubscribe(sid, &new_deliver_subject);
-
-                        if let Ok(new_sid) = result {
-                            shared_sid.store(new_sid, Ordering::Relaxed);
-
-                            let mut consumer_config = consumer_config.clone();
-                            consumer_config.deliver_subject = Some(new_deliver_subject);
-                            consumer_config.deliver_policy = DeliverPolicy::ByStartSeq;
-                            consumer_config.opt_start_seq = Some(start_seq);
-                            context.add_consumer(stream_name, consumer_config).ok();
-                        }
-
-                        let mut sequence_info = sequence_pair.lock();
-                        sequence_info.consumer_seq = 0;
-                    });
-
-                    true
-                }
-            };
-
             let context = self.clone();
+            let maybe_ordered = maybe_ordered.clone();
 
             move |sid: u64, message: &Message| {
-                if message.is_flow_control() {
-                    return false;
-                }
-
-                // Track and respond to idle heartbeats
+                // Respond to stalled consumers
                 if message.is_idle_heartbeat() {
                     let maybe_consumer_stalled = message
                         .headers
@@ -1324,45 +1571,13 @@ impl JetStream {
                             b"",
                         );
                     }
-
-                    // if it is not an ordered consumer, don't handle sequence mismatch.
-                    if is_ordered {
-                        return false;
-                    }
-
-                    let maybe_consumer_seq = message
-                        .headers
-                        .as_ref()
-                        .and_then(|headers| headers.get(header::NATS_LAST_CONSUMER));
-
-                    if let Some(consumer_seq) = maybe_consumer_seq {
-                        let consumer_seq = consumer_seq.parse::<u64>().unwrap();
-                        let sequence_info = sequence_pair.lock();
-                        if consumer_seq != sequence_info.consumer_seq {
-                            return handle_sequence_mismatch(sid, sequence_info.stream_seq + 1);
-                        }
-                    }
-
-                    return false;
                 }
 
                 // if it is not an ordered consumer, don't handle sequence mismatch.
-                if !is_ordered {
-                    return false;
-                }
-
-                // Track messages for sequence mismatches.
-                if let Some(message_info) = message.jetstream_message_info() {
-                    let mut sequence_info = sequence_pair.lock();
-                    if message_info.consumer_seq != sequence_info.consumer_seq + 1 {
-                        return handle_sequence_mismatch(sid, sequence_info.stream_seq + 1);
-                    }
-
-                    sequence_info.stream_seq = message_info.stream_seq;
-                    sequence_info.consumer_seq = message_info.consumer_seq;
+                match maybe_ordered.as_ref() {
+                    Some(ordered) => ordered.preprocess(sid, message),
+                    None => false,
                 }
-
-                false
             }
         };
 
@@ -1385,6 +1600,9 @@ impl JetStream {
             Box::new(preprocessor.clone()),
         )?;
 
+        // Store the sid right away, messages may arrive before the consumer creation returns.
+        shared_sid.store(sid, Ordering::Relaxed);
+
         // If we don't have a consumer yet we try to create one here.
         // If that fails try to bind once before giving up.
         let (consumer_info, consumer_ownership) = match maybe_consumer_info {
@@ -1444,13 +1662,21 @@ impl JetStream {
 
         shared_sid.store(sid, Ordering::Relaxed);
 
-        Ok(PushSubscription::new(
+        let subscription = PushSubscription::new(
             shared_sid,
+            shared_consumer,
             consumer_info,
             consumer_ownership,
             receiver,
+            maybe_options.and_then(|options| options.auto_progress),
             self.clone(),
-        ))
+        );
+
+        if let Some(ordered) = maybe_ordered {
+            *subscription.0.ordered_monitor.lock() = Some(ordered.monitor());
+        }
+
+        Ok(subscription)
     }
 
     /// Create a `JetStream` stream.
@@ -1489,6 +1715,33 @@ impl JetStream {
         PagedIterator {
             subject: format!("{}STREAM.NAMES", self.api_prefix()),
             manager: self,
+            filter: None,
+            offset: 0,
+            items: Default::default(),
+            done: false,
+        }
+    }
+
+    /// List the names of the `JetStream` streams capturing the given subject, which may contain
+    /// wildcards.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// for name in context.stream_names_by_subject("orders.created") {
+    ///     println!("orders.created is captured by {}", name?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stream_names_by_subject<S: AsRef<str>>(&self, subject: S) -> PagedIterator<'_, String> {
+        PagedIterator {
+            subject: format!("{}STREAM.NAMES", self.api_prefix()),
+            manager: self,
+            filter: Some(subject.as_ref().to_string()),
             offset: 0,
             items: Default::default(),
             done: false,
@@ -1518,12 +1771,53 @@ impl JetStream {
         PagedIterator {
             subject: format!("{}STREAM.LIST", self.api_prefix()),
             manager: self,
+            filter: None,
+            offset: 0,
+            items: Default::default(),
+            done: false,
+        }
+    }
+
+    /// List the `JetStream` streams capturing the given subject, which may contain wildcards.
+    pub fn list_streams_by_subject<S: AsRef<str>>(
+        &self,
+        subject: S,
+    ) -> PagedIterator<'_, StreamInfo> {
+        PagedIterator {
+            subject: format!("{}STREAM.LIST", self.api_prefix()),
+            manager: self,
+            filter: Some(subject.as_ref().to_string()),
             offset: 0,
             items: Default::default(),
             done: false,
         }
     }
 
+    /// List the names of the `JetStream` consumers for a stream. If you also want consumer
+    /// information, use the `list_consumers` method instead.
+    pub fn consumer_names<S>(&self, stream: S) -> io::Result<PagedIterator<'_, String>>
+    where
+        S: AsRef<str>,
+    {
+        let stream: &str = stream.as_ref();
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+        let subject: String = format!("{}CONSUMER.NAMES.{}", self.api_prefix(), stream);
+
+        Ok(PagedIterator {
+            subject,
+            manager: self,
+            filter: None,
+            offset: 0,
+            items: Default::default(),
+            done: false,
+        })
+    }
+
     /// List `JetStream` consumers for a stream.
     pub fn list_consumers<S>(&self, stream: S) -> io::Result<PagedIterator<'_, ConsumerInfo>>
     where
@@ -1541,6 +1835,7 @@ impl JetStream {
         Ok(PagedIterator {
             subject,
             manager: self,
+            filter: None,
             offset: 0,
             items: Default::default(),
             done: false,
@@ -1587,29 +1882,76 @@ impl JetStream {
             ));
         }
 
-        let subject = format!("{}STREAM.PURGE.{}", self.api_prefix(), stream);
-        let request = serde_json::to_vec(&PurgeRequest {
-            filter: Some(filter_subject.to_string()),
-            ..Default::default()
-        })?;
-
-        self.js_request(&subject, &request)
+        self.purge_stream_with_options(
+            stream,
+            &PurgeRequest {
+                filter: Some(filter_subject.to_string()),
+                ..Default::default()
+            },
+        )
     }
 
-    /// Get a message from a stream.
-    pub fn get_message<S: AsRef<str>>(&self, stream: S, seq: u64) -> io::Result<StreamMessage> {
-        let stream: &str = stream.as_ref();
-        if stream.is_empty() {
-            return Err(io::Error::new(
-                ErrorKind::InvalidInput,
-                "the stream name must not be empty",
-            ));
-        }
-
-        let subject = format!("{}STREAM.MSG.GET.{}", self.api_prefix(), stream);
-        let request = serde_json::ser::to_vec(&StreamMessageGetRequest {
+    /// Purge stream messages, optionally restricted to a subject, up to a sequence or keeping
+    /// the last messages.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use nats::jetstream::PurgeRequest;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// // Only keep the last 10 readings of the sensor.
+    /// context.purge_stream_with_options(
+    ///     "readings",
+    ///     &PurgeRequest {
+    ///         filter: Some("readings.sensor1".to_string()),
+    ///         keep: Some(10),
+    ///         ..Default::default()
+    ///     },
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn purge_stream_with_options<S: AsRef<str>>(
+        &self,
+        stream: S,
+        options: &PurgeRequest,
+    ) -> io::Result<PurgeResponse> {
+        let stream: &str = stream.as_ref();
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+        if options.sequence.is_some() && options.keep.is_some() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "sequence and keep cannot be combined",
+            ));
+        }
+
+        let subject = format!("{}STREAM.PURGE.{}", self.api_prefix(), stream);
+        let request = serde_json::to_vec(options)?;
+
+        self.js_request(&subject, &request)
+    }
+
+    /// Get a message from a stream.
+    pub fn get_message<S: AsRef<str>>(&self, stream: S, seq: u64) -> io::Result<StreamMessage> {
+        let stream: &str = stream.as_ref();
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+
+        let subject = format!("{}STREAM.MSG.GET.{}", self.api_prefix(), stream);
+        let request = serde_json::ser::to_vec(&StreamMessageGetRequest {
             seq: Some(seq),
-            last_by_subject: None,
+            ..Default::default()
         })?;
 
         let raw_message = self
@@ -1637,8 +1979,8 @@ impl JetStream {
 
         let subject = format!("{}STREAM.MSG.GET.{}", self.api_prefix(), stream_name);
         let request = serde_json::ser::to_vec(&StreamMessageGetRequest {
-            seq: None,
             last_by_subject: Some(stream_subject.to_string()),
+            ..Default::default()
         })?;
 
         let raw_message = self
@@ -1650,6 +1992,102 @@ impl JetStream {
         Ok(message)
     }
 
+    /// Get a message from a stream using the direct get API.
+    ///
+    /// Requires the stream to be created with `allow_direct`, which allows any replica to
+    /// respond instead of only the stream leader.
+    pub fn direct_get<S: AsRef<str>>(&self, stream: S, seq: u64) -> io::Result<StreamMessage> {
+        self.direct_get_request(
+            stream.as_ref(),
+            &StreamMessageGetRequest {
+                seq: Some(seq),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Get the last message for a subject from a stream using the direct get API.
+    pub fn direct_get_last_for_subject<S: AsRef<str>>(
+        &self,
+        stream: S,
+        subject: &str,
+    ) -> io::Result<StreamMessage> {
+        self.direct_get_request(
+            stream.as_ref(),
+            &StreamMessageGetRequest {
+                last_by_subject: Some(subject.to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Get the first message for a subject with a sequence greater or equal to the given one,
+    /// using the direct get API.
+    pub fn direct_get_next_for_subject<S: AsRef<str>>(
+        &self,
+        stream: S,
+        subject: &str,
+        seq: u64,
+    ) -> io::Result<StreamMessage> {
+        self.direct_get_request(
+            stream.as_ref(),
+            &StreamMessageGetRequest {
+                seq: Some(seq),
+                next_by_subject: Some(subject.to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn direct_get_request(
+        &self,
+        stream: &str,
+        request: &StreamMessageGetRequest,
+    ) -> io::Result<StreamMessage> {
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+
+        let subject = format!("{}DIRECT.GET.{}", self.api_prefix(), stream);
+        let request = serde_json::ser::to_vec(request)?;
+        let message = self.connection.request(&subject, &request)?;
+
+        // Errors are reported through a status header instead of a JSON response.
+        let maybe_status = message
+            .headers
+            .as_ref()
+            .and_then(|headers| headers.get(header::STATUS));
+
+        if let Some(status) = maybe_status {
+            let description = message
+                .headers
+                .as_ref()
+                .and_then(|headers| headers.get(header::DESCRIPTION))
+                .cloned();
+
+            if status == "404" {
+                return Err(io::Error::new(
+                    ErrorKind::Other,
+                    Error::new(404, ErrorCode::NoMessageFound, description),
+                ));
+            }
+
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                format!(
+                    "direct get failed with status {}: {}",
+                    status,
+                    description.unwrap_or_default()
+                ),
+            ));
+        }
+
+        StreamMessage::try_from(message)
+    }
+
     /// Delete message in a `JetStream` stream.
     pub fn delete_message<S: AsRef<str>>(
         &self,
@@ -1725,6 +2163,131 @@ impl JetStream {
         self.js_request(&subject, &ser_req)
     }
 
+    /// Create or update a durable `JetStream` consumer.
+    ///
+    /// Uses the create-or-update action of the server, so the consumer is created if it does not
+    /// exist yet. Only some of the configuration, such as `description`, `ack_wait`,
+    /// `max_deliver`, `backoff` or `metadata`, can be changed on an existing consumer.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use nats::jetstream::ConsumerConfig;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// let mut config = context.consumer_info("events", "processor")?.config;
+    /// config.max_deliver = 10;
+    /// context.update_consumer("events", &config)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_consumer<S, C>(&self, stream: S, config: C) -> io::Result<ConsumerInfo>
+    where
+        S: AsRef<str>,
+        ConsumerConfig: From<C>,
+    {
+        let config = ConsumerConfig::from(config);
+        let stream = stream.as_ref();
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+
+        let durable_name = match config.durable_name {
+            Some(ref durable_name) if !durable_name.is_empty() => durable_name,
+            _ => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "the durable name must not be empty",
+                ))
+            }
+        };
+
+        let subject = if !config.filter_subject.is_empty() && config.filter_subjects.is_empty() {
+            format!(
+                "{}CONSUMER.CREATE.{}.{}.{}",
+                self.api_prefix(),
+                stream,
+                durable_name,
+                config.filter_subject
+            )
+        } else {
+            format!(
+                "{}CONSUMER.CREATE.{}.{}",
+                self.api_prefix(),
+                stream,
+                durable_name
+            )
+        };
+
+        let req = CreateConsumerRequest {
+            stream_name: stream.into(),
+            config,
+        };
+
+        let ser_req = serde_json::ser::to_vec(&req)?;
+        self.js_request(&subject, &ser_req)
+    }
+
+    /// Pauses delivery of messages to a `JetStream` consumer until the given time.
+    ///
+    /// Requires nats-server 2.11 or newer.
+    pub fn pause_consumer<S, C>(
+        &self,
+        stream: S,
+        consumer: C,
+        pause_until: DateTime,
+    ) -> io::Result<PauseResponse>
+    where
+        S: AsRef<str>,
+        C: AsRef<str>,
+    {
+        self.request_consumer_pause(stream.as_ref(), consumer.as_ref(), Some(pause_until))
+    }
+
+    /// Resumes delivery of messages to a paused `JetStream` consumer.
+    ///
+    /// Requires nats-server 2.11 or newer.
+    pub fn resume_consumer<S, C>(&self, stream: S, consumer: C) -> io::Result<PauseResponse>
+    where
+        S: AsRef<str>,
+        C: AsRef<str>,
+    {
+        self.request_consumer_pause(stream.as_ref(), consumer.as_ref(), None)
+    }
+
+    fn request_consumer_pause(
+        &self,
+        stream: &str,
+        consumer: &str,
+        pause_until: Option<DateTime>,
+    ) -> io::Result<PauseResponse> {
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+        if consumer.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the consumer name must not be empty",
+            ));
+        }
+
+        let subject = format!(
+            "{}CONSUMER.PAUSE.{}.{}",
+            self.api_prefix(),
+            stream,
+            consumer
+        );
+        let req = serde_json::ser::to_vec(&ConsumerPauseRequest { pause_until })?;
+        self.js_request(&subject, &req)
+    }
+
     /// Delete a `JetStream` consumer.
     pub fn delete_consumer<S, C>(&self, stream: S, consumer: C) -> io::Result<bool>
     where
@@ -1775,28 +2338,348 @@ impl JetStream {
         self.js_request(&subject, b"")
     }
 
+    /// Takes a snapshot of a stream, including its consumers, and writes the resulting tarball to
+    /// `writer`.
+    ///
+    /// Returns the configuration and state of the stream at the time of the snapshot, which can be
+    /// used to restore it later with `restore_stream`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// let mut file = std::fs::File::create("events.tar.s2")?;
+    /// let snapshot = context.snapshot_stream("events", &mut file)?;
+    ///
+    /// println!("saved {} messages", snapshot.state.messages);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn snapshot_stream<S, W>(&self, stream: S, mut writer: W) -> io::Result<StreamSnapshotInfo>
+    where
+        S: AsRef<str>,
+        W: io::Write,
+    {
+        let stream: &str = stream.as_ref();
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+
+        let deliver_subject = self.connection.new_inbox();
+        let subscription = self.connection.subscribe(&deliver_subject)?;
+
+        let subject = format!("{}STREAM.SNAPSHOT.{}", self.api_prefix(), stream);
+        let request = serde_json::to_vec(&StreamSnapshotRequest {
+            deliver_subject,
+            no_consumers: false,
+            chunk_size: SNAPSHOT_CHUNK_SIZE,
+            check_msgs: false,
+        })?;
+
+        let info: StreamSnapshotInfo = self.js_request(&subject, &request)?;
+
+        loop {
+            let message = subscription.next_timeout(SNAPSHOT_CHUNK_TIMEOUT)?;
+
+            // An empty message marks the end of the snapshot, unless it carries an error status.
+            if message.data.is_empty() {
+                let maybe_headers = message.headers.as_ref();
+                if let Some(status) = maybe_headers.and_then(|headers| headers.get(header::STATUS))
+                {
+                    let description = maybe_headers
+                        .and_then(|headers| headers.get(header::DESCRIPTION))
+                        .cloned()
+                        .unwrap_or_default();
+
+                    return Err(io::Error::new(
+                        ErrorKind::Other,
+                        format!("snapshot failed with status {}: {}", status, description),
+                    ));
+                }
+
+                break;
+            }
+
+            writer.write_all(&message.data)?;
+
+            // Acknowledge the chunk so the server keeps sending.
+            if message.reply.is_some() {
+                message.respond(b"")?;
+            }
+        }
+
+        subscription.unsubscribe()?;
+        writer.flush()?;
+
+        Ok(info)
+    }
+
+    /// Restores a stream from a snapshot tarball produced by `snapshot_stream`, read from `reader`.
+    ///
+    /// The stream must not exist yet.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use nats::jetstream::StreamConfig;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// let file = std::fs::File::open("events.tar.s2")?;
+    /// let info = context.restore_stream(&StreamConfig::from("events"), file)?;
+    ///
+    /// println!("restored {} messages", info.state.messages);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn restore_stream<R: io::Read>(
+        &self,
+        config: &StreamConfig,
+        mut reader: R,
+    ) -> io::Result<StreamInfo> {
+        if config.name.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+
+        let subject = format!("{}STREAM.RESTORE.{}", self.api_prefix(), config.name);
+        let request = serde_json::to_vec(&StreamRestoreRequest {
+            config: config.clone(),
+        })?;
+
+        let response: StreamRestoreResponse = self.js_request(&subject, &request)?;
+
+        let mut buffer = vec![0; SNAPSHOT_CHUNK_SIZE];
+        loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+
+            // Every chunk is acknowledged by the server with an empty message, or an API error.
+            let message = self.connection.request_timeout(
+                &response.deliver_subject,
+                &buffer[..n],
+                SNAPSHOT_CHUNK_TIMEOUT,
+            )?;
+
+            if !message.data.is_empty() {
+                parse_api_response::<serde_json::Value>(&message.data)?;
+            }
+        }
+
+        // An empty message marks the end of the snapshot, the server then replies with the
+        // restored stream.
+        let message = self.connection.request_timeout(
+            &response.deliver_subject,
+            b"",
+            SNAPSHOT_RESTORE_TIMEOUT,
+        )?;
+
+        parse_api_response(&message.data)
+    }
+
     /// Query `JetStream` account information.
     pub fn account_info(&self) -> io::Result<AccountInfo> {
         self.js_request(&format!("{}INFO", self.api_prefix()), b"")
     }
 
+    /// Subscribes to the advisories published by the server, such as messages exceeding their
+    /// maximum deliveries or streams and consumers being created and deleted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use nats::jetstream::Advisory;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// for advisory in context.advisories()? {
+    ///     if let Advisory::MaxDeliveries(advisory) = advisory? {
+    ///         let message = context.get_message(&advisory.stream, advisory.stream_seq)?;
+    ///         println!("dead letter: {:?}", message);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn advisories(&self) -> io::Result<Advisories> {
+        self.connection
+            .subscribe(ADVISORY_SUBJECT)
+            .map(Advisories::new)
+    }
+
+    /// Subscribes to the metrics published by the server, such as sampled acknowledgements of
+    /// consumers with a `sample_frequency`.
+    pub fn metrics(&self) -> io::Result<Advisories> {
+        self.connection
+            .subscribe(METRIC_SUBJECT)
+            .map(Advisories::new)
+    }
+
+    /// Asks the current leader of a clustered stream to step down, triggering the election of a
+    /// new leader. Returns the cluster information of the stream once another leader is reported.
+    ///
+    /// The election happens asynchronously. If no other leader is reported in time, the latest
+    /// cluster information is returned, with either the previous leader or an empty one.
+    pub fn stream_leader_stepdown<S: AsRef<str>>(&self, stream: S) -> io::Result<ClusterInfo> {
+        let stream: &str = stream.as_ref();
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+
+        let previous = self.stream_info(stream)?.cluster.leader;
+
+        let subject = format!("{}STREAM.LEADER.STEPDOWN.{}", self.api_prefix(), stream);
+        let response: SuccessResponse = self.js_request(&subject, b"")?;
+        if !response.success {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "stream leader stepdown was not successful",
+            ));
+        }
+
+        self.wait_for_cluster(
+            || self.stream_info(stream).map(|info| info.cluster),
+            |cluster| !cluster.leader.is_empty() && cluster.leader != previous,
+        )
+    }
+
+    /// Asks the current leader of a clustered consumer to step down, triggering the election of a
+    /// new leader. Returns the cluster information of the consumer once another leader is
+    /// reported.
+    ///
+    /// The election happens asynchronously. If no other leader is reported in time, the latest
+    /// cluster information is returned, with either the previous leader or an empty one.
+    pub fn consumer_leader_stepdown<S, C>(&self, stream: S, consumer: C) -> io::Result<ClusterInfo>
+    where
+        S: AsRef<str>,
+        C: AsRef<str>,
+    {
+        let stream: &str = stream.as_ref();
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+        let consumer: &str = consumer.as_ref();
+        if consumer.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the consumer name must not be empty",
+            ));
+        }
+
+        let previous = self.consumer_info(stream, consumer)?.cluster.leader;
+
+        let subject = format!(
+            "{}CONSUMER.LEADER.STEPDOWN.{}.{}",
+            self.api_prefix(),
+            stream,
+            consumer
+        );
+        let response: SuccessResponse = self.js_request(&subject, b"")?;
+        if !response.success {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "consumer leader stepdown was not successful",
+            ));
+        }
+
+        self.wait_for_cluster(
+            || {
+                self.consumer_info(stream, consumer)
+                    .map(|info| info.cluster)
+            },
+            |cluster| !cluster.leader.is_empty() && cluster.leader != previous,
+        )
+    }
+
+    /// Removes a peer from the raft group of a clustered stream, for example a server which is
+    /// permanently gone. The server will place a new replica elsewhere if possible.
+    ///
+    /// Returns the cluster information of the stream once the peer is no longer reported as a
+    /// member and a leader is reported. If that does not happen in time, the latest cluster
+    /// information is returned.
+    pub fn stream_remove_peer<S, P>(&self, stream: S, peer: P) -> io::Result<ClusterInfo>
+    where
+        S: AsRef<str>,
+        P: AsRef<str>,
+    {
+        let stream: &str = stream.as_ref();
+        if stream.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the stream name must not be empty",
+            ));
+        }
+        let peer: &str = peer.as_ref();
+        if peer.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the peer name must not be empty",
+            ));
+        }
+
+        let subject = format!("{}STREAM.PEER.REMOVE.{}", self.api_prefix(), stream);
+        let request = serde_json::to_vec(&PeerRemoveRequest {
+            peer: peer.to_string(),
+        })?;
+        let response: SuccessResponse = self.js_request(&subject, &request)?;
+        if !response.success {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "stream peer removal was not successful",
+            ));
+        }
+
+        self.wait_for_cluster(
+            || self.stream_info(stream).map(|info| info.cluster),
+            |cluster| {
+                !cluster.leader.is_empty()
+                    && cluster.leader != peer
+                    && cluster.replicas.iter().all(|replica| replica.name != peer)
+            },
+        )
+    }
+
+    // Polls the cluster information of a raft group until it settles, as decided by `settled`.
+    // Errors are retried as well, since info requests can fail while an election is in progress.
+    fn wait_for_cluster<F, P>(&self, mut cluster_info: F, settled: P) -> io::Result<ClusterInfo>
+    where
+        F: FnMut() -> io::Result<ClusterInfo>,
+        P: Fn(&ClusterInfo) -> bool,
+    {
+        let deadline = Instant::now() + LEADER_ELECTION_TIMEOUT;
+        loop {
+            let result = cluster_info();
+            match result {
+                Ok(ref cluster) if settled(cluster) => return result,
+                _ if Instant::now() >= deadline => return result,
+                _ => thread::sleep(LEADER_ELECTION_POLL_INTERVAL),
+            }
+        }
+    }
+
     fn js_request<Res>(&self, subject: &str, req: &[u8]) -> io::Result<Res>
     where
         Res: DeserializeOwned,
     {
         let res_msg = self.connection.request(subject, req)?;
-        let res: ApiResponse<Res> = serde_json::de::from_slice(&res_msg.data)?;
-        match res {
-            ApiResponse::Ok(stream_info) => Ok(stream_info),
-            ApiResponse::Err { error, .. } => {
-                log::error!(
-                    "failed to parse API response: {:?}",
-                    std::str::from_utf8(&res_msg.data)
-                );
-
-                Err(io::Error::new(io::ErrorKind::Other, error))
-            }
-        }
+        parse_api_response(&res_msg.data)
     }
 
     fn api_prefix(&self) -> &str {
@@ -1804,6 +2687,75 @@ impl JetStream {
     }
 }
 
+/// Parses a `JetStream` API response, turning API errors into an `io::Error`.
+fn parse_api_response<Res>(data: &[u8]) -> io::Result<Res>
+where
+    Res: DeserializeOwned,
+{
+    let res: ApiResponse<Res> = serde_json::de::from_slice(data)?;
+    match res {
+        ApiResponse::Ok(response) => Ok(response),
+        ApiResponse::Err { error, .. } => {
+            log::error!(
+                "failed to parse API response: {:?}",
+                std::str::from_utf8(data)
+            );
+
+            Err(io::Error::new(io::ErrorKind::Other, error))
+        }
+    }
+}
+
+/// Adds the headers required by the given publish options, if any.
+fn headers_with_options(
+    maybe_options: Option<&PublishOptions>,
+    maybe_headers: Option<&HeaderMap>,
+) -> Option<HeaderMap> {
+    if let Some(options) = maybe_options {
+        let mut headers = maybe_headers.map_or_else(HeaderMap::default, HeaderMap::clone);
+
+        if let Some(v) = options.id.as_ref() {
+            headers.insert(header::NATS_MSG_ID, v.to_string());
+        }
+
+        if let Some(v) = options.expected_last_msg_id.as_ref() {
+            headers.insert(header::NATS_EXPECTED_LAST_MSG_ID, v.to_string());
+        }
+
+        if let Some(v) = options.expected_stream.as_ref() {
+            headers.insert(header::NATS_EXPECTED_STREAM, v.to_string());
+        }
+
+        if let Some(v) = options.expected_last_sequence.as_ref() {
+            headers.insert(header::NATS_EXPECTED_LAST_SEQUENCE, v.to_string());
+        }
+
+        if let Some(v) = options.expected_last_subject_sequence.as_ref() {
+            headers.insert(header::NATS_EXPECTED_LAST_SUBJECT_SEQUENCE, v.to_string());
+        }
+
+        Some(headers)
+    } else {
+        maybe_headers.cloned()
+    }
+}
+
+/// Parses the response to a published message.
+fn parse_publish_ack(data: &[u8]) -> io::Result<PublishAck> {
+    let res: ApiResponse<PublishAck> = serde_json::de::from_slice(data)?;
+    match res {
+        ApiResponse::Ok(pub_ack) => Ok(pub_ack),
+        ApiResponse::Err { error, .. } => {
+            log::debug!(
+                "failed to parse API response: {:?}",
+                std::str::from_utf8(data)
+            );
+
+            Err(io::Error::new(ErrorKind::Other, error))
+        }
+    }
+}
+
 /// Creates a new `JetStream` context using the given `Connection` and default options.
 ///
 pub fn new(nc: Connection) -> JetStream {
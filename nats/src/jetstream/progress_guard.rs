@@ -0,0 +1,85 @@
+// Copyright 2020-2022 The NATS Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crossbeam_channel as channel;
+
+use crate::jetstream::AckKind;
+use crate::Message;
+
+// The shortest interval between in-progress acknowledgements, so a zero interval does not spin.
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sends in-progress acknowledgements for a message from a background thread until it is
+/// dropped, see `Message::auto_progress`.
+pub struct ProgressGuard {
+    stop: Option<channel::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for ProgressGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ProgressGuard").finish()
+    }
+}
+
+impl ProgressGuard {
+    pub(crate) fn new(message: Message, interval: Duration) -> ProgressGuard {
+        let interval = interval.max(MIN_INTERVAL);
+        let (stop, stopped) = channel::bounded::<()>(1);
+
+        let thread = thread::Builder::new()
+            .name("nats_jetstream_auto_progress".to_string())
+            .spawn(move || loop {
+                match stopped.recv_timeout(interval) {
+                    Err(channel::RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
+
+                if message.acknowledged.load(Ordering::Acquire)
+                    || message.double_acked.load(Ordering::Acquire)
+                {
+                    return;
+                }
+
+                if let Err(err) = message.ack_kind(AckKind::Progress) {
+                    log::error!("failed to send in-progress acknowledgement: {}", err);
+                    return;
+                }
+            })
+            .expect("threads should be spawnable");
+
+        ProgressGuard {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops sending in-progress acknowledgements. Same as dropping the guard.
+    pub fn stop(self) {}
+}
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the thread, wait for it so no acknowledgement is sent
+        // after the guard is gone.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
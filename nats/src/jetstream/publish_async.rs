@@ -0,0 +1,322 @@
+// Copyright 2020-2022 The NATS Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel as channel;
+use parking_lot::{Condvar, Mutex};
+
+use crate::header::HeaderMap;
+use crate::jetstream::{parse_publish_ack, PublishAck};
+use crate::{Connection, Handler, Message};
+
+/// How long a publisher is stalled waiting for room in the pending window before giving up.
+const STALL_WAIT: Duration = Duration::from_millis(200);
+
+/// How many times a message is republished when there are no responders, for example
+/// during a leader election.
+const NO_RESPONDERS_RETRY_ATTEMPTS: usize = 2;
+
+/// How long to wait before republishing a message which had no responders.
+const NO_RESPONDERS_RETRY_WAIT: Duration = Duration::from_millis(250);
+
+/// A message which has been published but not yet acknowledged by the server.
+struct PendingAck {
+    subject: String,
+    headers: Option<HeaderMap>,
+    data: Vec<u8>,
+    attempts: usize,
+    deadline: Instant,
+    sender: channel::Sender<io::Result<PublishAck>>,
+}
+
+/// Outstanding acknowledgements keyed by the reply token of each message.
+#[derive(Default)]
+struct PendingAcks {
+    acks: Mutex<HashMap<String, PendingAck>>,
+    changed: Condvar,
+}
+
+impl PendingAcks {
+    /// Fails the acknowledgements whose deadline has passed, returning how many expired.
+    fn expire(&self, acks: &mut HashMap<String, PendingAck>) -> usize {
+        let now = Instant::now();
+        let before = acks.len();
+        acks.retain(|_, ack| {
+            if ack.deadline > now {
+                return true;
+            }
+
+            ack.sender
+                .send(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out waiting for publish ack",
+                )))
+                .ok();
+            false
+        });
+
+        let expired = before - acks.len();
+        if expired > 0 {
+            self.changed.notify_all();
+        }
+        expired
+    }
+}
+
+/// Returns the earliest deadline of the pending acknowledgements and the given one.
+fn next_deadline(acks: &HashMap<String, PendingAck>, maybe_deadline: Option<Instant>) -> Instant {
+    acks.values()
+        .map(|ack| ack.deadline)
+        .chain(maybe_deadline)
+        .min()
+        .unwrap_or_else(Instant::now)
+}
+
+/// Shared state for asynchronous publishing. A single reply subscription is used for all
+/// messages published through a `JetStream` context and its clones.
+pub(crate) struct PublishAsync {
+    prefix: String,
+    max_pending: usize,
+    timeout: Duration,
+    pending: Arc<PendingAcks>,
+    handler: Option<Handler>,
+}
+
+impl fmt::Debug for PublishAsync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("PublishAsync")
+            .field("prefix", &self.prefix)
+            .field("max_pending", &self.max_pending)
+            .field("pending", &self.pending.acks.lock().len())
+            .finish()
+    }
+}
+
+impl Drop for PublishAsync {
+    fn drop(&mut self) {
+        if let Some(handler) = self.handler.take() {
+            handler.unsubscribe().ok();
+        }
+    }
+}
+
+impl PublishAsync {
+    /// Subscribes to a wildcard reply inbox and starts dispatching acknowledgements.
+    pub(crate) fn new(
+        connection: &Connection,
+        max_pending: usize,
+        timeout: Duration,
+    ) -> io::Result<PublishAsync> {
+        let prefix = format!("{}.", connection.new_inbox());
+        let pending = Arc::new(PendingAcks::default());
+
+        let handler = {
+            let prefix = prefix.clone();
+            let pending = pending.clone();
+            let connection = connection.clone();
+
+            connection
+                .subscribe(&format!("{}*", prefix))?
+                .with_handler(move |message: Message| {
+                    let token = match message.subject.strip_prefix(&prefix) {
+                        Some(token) => token,
+                        None => return Ok(()),
+                    };
+
+                    let mut acks = pending.acks.lock();
+
+                    // Republish if there were no responders, which is expected while a stream
+                    // is electing a new leader.
+                    if message.is_no_responders() {
+                        if let Some(ack) = acks.get_mut(token) {
+                            if ack.attempts < NO_RESPONDERS_RETRY_ATTEMPTS {
+                                ack.attempts += 1;
+
+                                let connection = connection.clone();
+                                let subject = ack.subject.clone();
+                                let headers = ack.headers.clone();
+                                let data = ack.data.clone();
+                                let reply = message.subject.clone();
+
+                                thread::spawn(move || {
+                                    thread::sleep(NO_RESPONDERS_RETRY_WAIT);
+                                    connection
+                                        .publish_with_reply_or_headers(
+                                            &subject,
+                                            Some(&reply),
+                                            headers.as_ref(),
+                                            data,
+                                        )
+                                        .ok();
+                                });
+
+                                return Ok(());
+                            }
+                        }
+                    }
+
+                    if let Some(ack) = acks.remove(token) {
+                        let result = if message.is_no_responders() {
+                            Err(io::Error::new(io::ErrorKind::NotFound, "no responders"))
+                        } else {
+                            parse_publish_ack(&message.data)
+                        };
+
+                        ack.sender.send(result).ok();
+                        pending.changed.notify_all();
+                    }
+
+                    Ok(())
+                })
+        };
+
+        Ok(PublishAsync {
+            prefix,
+            max_pending,
+            timeout,
+            pending,
+            handler: Some(handler),
+        })
+    }
+
+    /// Publishes a message, blocking briefly if the window of pending acknowledgements is full.
+    pub(crate) fn publish(
+        &self,
+        connection: &Connection,
+        subject: &str,
+        headers: Option<&HeaderMap>,
+        data: &[u8],
+    ) -> io::Result<PendingPublishAck> {
+        let token = nuid::next();
+        let reply = format!("{}{}", self.prefix, token);
+        let (sender, receiver) = channel::bounded(1);
+
+        {
+            let mut acks = self.pending.acks.lock();
+            let deadline = Instant::now() + STALL_WAIT;
+            self.pending.expire(&mut acks);
+            while acks.len() >= self.max_pending {
+                let next_deadline = next_deadline(&acks, Some(deadline));
+                self.pending.changed.wait_until(&mut acks, next_deadline);
+                self.pending.expire(&mut acks);
+                if acks.len() >= self.max_pending && Instant::now() >= deadline {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WouldBlock,
+                        "stalled with too many outstanding async published messages",
+                    ));
+                }
+            }
+
+            acks.insert(
+                token.clone(),
+                PendingAck {
+                    subject: subject.to_string(),
+                    headers: headers.cloned(),
+                    data: data.to_vec(),
+                    attempts: 0,
+                    deadline: Instant::now() + self.timeout,
+                    sender,
+                },
+            );
+        }
+
+        let result = connection.publish_with_reply_or_headers(subject, Some(&reply), headers, data);
+
+        if let Err(err) = result {
+            self.pending.acks.lock().remove(&token);
+            self.pending.changed.notify_all();
+            return Err(err);
+        }
+
+        Ok(PendingPublishAck {
+            receiver,
+            deadline: Instant::now() + self.timeout,
+        })
+    }
+
+    /// Returns the number of messages which are still waiting for an acknowledgement.
+    pub(crate) fn pending(&self) -> usize {
+        let mut acks = self.pending.acks.lock();
+        self.pending.expire(&mut acks);
+        acks.len()
+    }
+
+    /// Blocks until every pending message has been acknowledged or expired, or the deadline
+    /// passes. Returns a timeout error if any acknowledgement expired while waiting.
+    pub(crate) fn complete(&self, maybe_deadline: Option<Instant>) -> io::Result<()> {
+        let mut acks = self.pending.acks.lock();
+        let mut expired = self.pending.expire(&mut acks);
+        while !acks.is_empty() {
+            if maybe_deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out waiting for pending acks",
+                ));
+            }
+
+            let deadline = next_deadline(&acks, maybe_deadline);
+            self.pending.changed.wait_until(&mut acks, deadline);
+            expired += self.pending.expire(&mut acks);
+        }
+
+        if expired > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} pending acks timed out", expired),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// A handle to the acknowledgement of a message published with `JetStream::publish_async`.
+#[derive(Debug)]
+pub struct PendingPublishAck {
+    receiver: channel::Receiver<io::Result<PublishAck>>,
+    deadline: Instant,
+}
+
+impl PendingPublishAck {
+    /// Blocks until the server acknowledges the message, or the publish timeout passes.
+    pub fn wait(self) -> io::Result<PublishAck> {
+        let timeout = self.deadline.saturating_duration_since(Instant::now());
+        self.wait_timeout(timeout)
+    }
+
+    /// Blocks until the server acknowledges the message, or the timeout passes.
+    pub fn wait_timeout(self, timeout: Duration) -> io::Result<PublishAck> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(channel::RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "wait_timeout: timed out",
+            )),
+            Err(channel::RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "publish ack channel closed",
+            )),
+        }
+    }
+
+    /// Returns the acknowledgement if it has already been received.
+    pub fn try_wait(&self) -> Option<io::Result<PublishAck>> {
+        self.receiver.try_recv().ok()
+    }
+}
@@ -11,25 +11,36 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::error;
+use std::fmt;
 use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::jetstream::{ConsumerInfo, ConsumerOwnership, JetStream};
+use crate::header;
+use crate::jetstream::{
+    ConsumerConfig, ConsumerInfo, ConsumerOwnership, DeliverPolicy, JetStream, SequencePair,
+};
 use crate::Message;
 
-use super::{AckPolicy, BatchOptions};
+use super::{AckPolicy, BatchOptions, MessagesOptions, PullSubscribeOptions};
 use crossbeam_channel as channel;
+use parking_lot::Mutex;
 
 #[derive(Debug)]
 pub(crate) struct Inner {
-    pid: u64,
+    /// sid of the inbox subscription, which changes when an ordered consumer is recreated.
+    pid: AtomicU64,
 
     /// messages channel for this subscription.
     pub(crate) messages: channel::Receiver<Message>,
 
-    /// sid of the inbox subscription
-    pub(crate) inbox: String,
+    /// Inbox the messages are delivered to, which changes when an ordered consumer is recreated.
+    pub(crate) inbox: Mutex<String>,
+
+    /// Name of the consumer, which changes when an ordered consumer is recreated.
+    pub(crate) consumer: Mutex<String>,
 
     /// Ack policy used in methods that automatically ack.
     pub(crate) consumer_ack_policy: AckPolicy,
@@ -40,23 +51,68 @@ pub(crate) struct Inner {
     /// Indicates if we own the consumer and are responsible for deleting it or not.
     pub(crate) consumer_ownership: ConsumerOwnership,
 
+    /// Interval of in-progress acknowledgements sent while handlers run, if any.
+    pub(crate) auto_progress: Option<Duration>,
+
+    /// Sequence tracking of an ordered consumer, if the consumer is ordered.
+    ordered: Option<OrderedPull>,
+
     /// Client associated with subscription.
     pub(crate) context: JetStream,
 }
 
 impl Drop for Inner {
     fn drop(&mut self) {
-        self.context.connection.0.client.unsubscribe(self.pid).ok();
+        self.context
+            .connection
+            .0
+            .client
+            .unsubscribe(self.pid.load(Ordering::Relaxed))
+            .ok();
 
         // Delete the consumer, if we own it.
         if self.consumer_ownership == ConsumerOwnership::Yes {
             self.context
-                .delete_consumer(&self.info.stream_name, &self.info.name)
+                .delete_consumer(&self.info.stream_name, &*self.consumer.lock())
                 .ok();
         }
     }
 }
 
+/// Keeps an ordered pull consumer in sequence, see `PullSubscribeOptions::ordered`.
+#[derive(Debug)]
+struct OrderedPull {
+    config: ConsumerConfig,
+    sequence_pair: Mutex<SequencePair>,
+}
+
+impl OrderedPull {
+    fn new(config: ConsumerConfig) -> OrderedPull {
+        OrderedPull {
+            config,
+            sequence_pair: Mutex::new(SequencePair {
+                consumer_seq: 0,
+                stream_seq: 0,
+            }),
+        }
+    }
+
+    /// Tracks the sequences of a message, returning false if it is out of sequence.
+    fn track(&self, message: &Message) -> bool {
+        if let Some(message_info) = message.jetstream_message_info() {
+            let mut sequence_pair = self.sequence_pair.lock();
+            if message_info.consumer_seq != sequence_pair.consumer_seq + 1 {
+                return false;
+            }
+
+            sequence_pair.stream_seq = message_info.stream_seq;
+            sequence_pair.consumer_seq = message_info.consumer_seq;
+        }
+
+        true
+    }
+}
+
 /// A `PullSubscription` pulls messages from Server triggered by client actions
 /// Pull Subscription does nothing on itself. It has to explicitly request messages
 /// using one of available
@@ -71,13 +127,22 @@ impl PullSubscription {
         consumer_ownership: ConsumerOwnership,
         inbox: String,
         messages: channel::Receiver<Message>,
+        maybe_options: Option<&PullSubscribeOptions>,
         context: JetStream,
     ) -> PullSubscription {
+        // Ordered consumers are recreated with the configuration the server reported.
+        let ordered = maybe_options
+            .filter(|options| options.ordered)
+            .map(|_| OrderedPull::new(consumer_info.config.clone()));
+
         PullSubscription(Arc::new(Inner {
-            pid,
-            inbox,
+            pid: AtomicU64::new(pid),
+            inbox: Mutex::new(inbox),
+            consumer: Mutex::new(consumer_info.name.clone()),
             messages,
             consumer_ownership,
+            auto_progress: maybe_options.and_then(|options| options.auto_progress),
+            ordered,
             consumer_ack_policy: consumer_info.config.ack_policy,
             info: consumer_info,
             context,
@@ -91,6 +156,9 @@ impl PullSubscription {
     /// also `expires` and `no_wait`.
     /// If `no_wait` will be specified, iterator will also return when there are no more messages
     /// in the Consumer.
+    /// The iterator also ends when the server ends the request with a status, or when an
+    /// `idle_heartbeat` was requested and the server stopped sending them. The reason is
+    /// available through [`BatchIter::status`].
     ///
     /// # Example
     /// ```
@@ -112,9 +180,9 @@ impl PullSubscription {
     ///
     /// // pass whole `BatchOptions` to fetch
     /// let messages = consumer.fetch(BatchOptions{
-    ///     expires: None,
-    ///     no_wait: false,
     ///     batch: 10,
+    ///     max_bytes: 1024 * 1024,
+    ///     ..Default::default()
     /// })?;
     /// for message in messages {
     ///     println!("received message {:?}", message);
@@ -128,6 +196,8 @@ impl PullSubscription {
         Ok(BatchIter {
             batch_size: batch_options.batch,
             processed: 0,
+            idle_heartbeat: batch_options.idle_heartbeat,
+            status: None,
             subscription: self,
         })
     }
@@ -142,6 +212,9 @@ impl PullSubscription {
     /// also `expires` and `no_wait`.
     /// If `no_wait` will be specified, iterator will also return when there are no more messages
     /// in the Consumer.
+    /// `404 No Messages` and `408 Request Timeout` statuses end the iterator, while other statuses
+    /// and missed idle heartbeats are yielded once as an error wrapping a [`PullStatus`]. The
+    /// reason is also available through [`TimeoutBatchIter::status`].
     ///
     /// # Example
     /// ```
@@ -164,9 +237,9 @@ impl PullSubscription {
     ///
     /// // pass whole `BatchOptions` to fetch
     /// let messages = consumer.timeout_fetch(BatchOptions{
-    ///     expires: None,
-    ///     no_wait: false,
     ///     batch: 10,
+    ///     idle_heartbeat: Duration::from_secs(5),
+    ///     ..Default::default()
     /// }, Duration::from_millis(100))?;
     /// for message in messages {
     ///     println!("received message {:?}", message);
@@ -185,6 +258,8 @@ impl PullSubscription {
             timeout,
             batch_size: batch_options.batch,
             processed: 0,
+            idle_heartbeat: batch_options.idle_heartbeat,
+            status: None,
             subscription: self,
         })
     }
@@ -192,6 +267,9 @@ impl PullSubscription {
     /// High level method that fetches given set of messages, processes them in user-provider
     /// closure and acks them automatically according to `Consumer` `AckPolicy`.
     ///
+    /// If the batch ends early for any reason other than running out of messages or time, such
+    /// as a deleted consumer or a rejected request, an error wrapping a `PullStatus` is returned.
+    ///
     /// # Example
     /// ```
     /// # use nats::jetstream::BatchOptions;
@@ -216,9 +294,15 @@ impl PullSubscription {
     {
         let mut last_message;
         let consumer_ack_policy = self.0.consumer_ack_policy;
-        let batch = self.fetch(batch)?;
-        for message in batch {
-            handler(&message)?;
+        let mut batch = self.fetch(batch)?;
+        for message in &mut batch {
+            {
+                let _progress = self
+                    .0
+                    .auto_progress
+                    .map(|interval| message.auto_progress(interval));
+                handler(&message)?;
+            }
             if consumer_ack_policy != AckPolicy::None {
                 message.ack()?
             }
@@ -231,6 +315,14 @@ impl PullSubscription {
                 }
             }
         }
+
+        // Like `timeout_fetch`, only running out of messages or time ends a batch normally.
+        if let Some(status) = batch.status() {
+            if !status.is_end_of_batch() {
+                return Err(status.clone().into());
+            }
+        }
+
         Ok(())
     }
 
@@ -256,7 +348,12 @@ impl PullSubscription {
     /// # }
     /// ```
     pub fn next(&self) -> Option<Message> {
-        self.preprocess(self.0.messages.recv().ok())
+        loop {
+            match self.0.messages.recv() {
+                Ok(message) if message.is_idle_heartbeat() => {}
+                message => return self.preprocess(message.ok()),
+            }
+        }
     }
 
     /// A low level method that should be used only in specific cases.
@@ -282,7 +379,12 @@ impl PullSubscription {
     /// # }
     /// ```
     pub fn try_next(&self) -> Option<Message> {
-        self.preprocess(self.0.messages.try_recv().ok())
+        loop {
+            match self.0.messages.try_recv() {
+                Ok(message) if message.is_idle_heartbeat() => {}
+                message => return self.preprocess(message.ok()),
+            }
+        }
     }
 
     /// A low level method that should be used only in specific cases.
@@ -318,7 +420,7 @@ impl PullSubscription {
     pub fn next_timeout(&self, timeout: Duration) -> io::Result<Message> {
         match self.0.messages.recv_timeout(timeout) {
             Ok(message) => {
-                if message.is_no_messages() {
+                if message.is_no_messages() || message.is_idle_heartbeat() {
                     return self.next_timeout(timeout);
                 }
                 if message.is_request_timeout() {
@@ -359,9 +461,8 @@ impl PullSubscription {
     ///
     /// // request messages specifying whole config.
     /// consumer.request_batch(BatchOptions{
-    ///     expires: None,
-    ///     no_wait: false,
     ///     batch: 10,
+    ///     ..Default::default()
     /// })?;
     /// # Ok(())
     /// # }
@@ -373,20 +474,74 @@ impl PullSubscription {
             "{}CONSUMER.MSG.NEXT.{}.{}",
             self.0.context.api_prefix(),
             self.0.info.stream_name,
-            self.0.info.name,
+            self.0.consumer.lock(),
         );
 
         let request = serde_json::to_vec(&batch_opts)?;
 
+        let inbox = self.0.inbox.lock().clone();
         self.0.context.connection.publish_with_reply_or_headers(
             &subject,
-            Some(self.0.inbox.as_str()),
+            Some(inbox.as_str()),
             None,
             request,
         )?;
         Ok(())
     }
 
+    /// Recreates an ordered consumer right after the last stream sequence that was delivered.
+    ///
+    /// Messages are delivered to a new inbox, so nothing sent to the previous consumer gets mixed
+    /// in, including the statuses of its outstanding pull requests.
+    fn recreate_ordered(&self, ordered: &OrderedPull) -> io::Result<()> {
+        let stream_seq = {
+            let mut sequence_pair = ordered.sequence_pair.lock();
+            sequence_pair.consumer_seq = 0;
+            sequence_pair.stream_seq
+        };
+
+        let client = &self.0.context.connection.0.client;
+        let inbox = self.0.context.connection.new_inbox();
+        let pid = client.resubscribe(self.0.pid.load(Ordering::Relaxed), &inbox)?;
+        self.0.pid.store(pid, Ordering::Relaxed);
+        *self.0.inbox.lock() = inbox;
+
+        // Drop whatever the previous inbox already received.
+        while self.0.messages.try_recv().is_ok() {}
+
+        let old_consumer = self.0.consumer.lock().clone();
+        if !old_consumer.is_empty() {
+            self.0
+                .context
+                .delete_consumer(&self.0.info.stream_name, &old_consumer)
+                .ok();
+        }
+
+        // Continue right after the last delivered message, or start over if nothing was
+        // delivered yet.
+        let mut config = ordered.config.clone();
+        if stream_seq > 0 {
+            config.deliver_policy = DeliverPolicy::ByStartSeq;
+            config.opt_start_seq = Some(stream_seq + 1);
+            config.opt_start_time = None;
+        }
+
+        match self
+            .0
+            .context
+            .add_consumer(&self.0.info.stream_name, config)
+        {
+            Ok(info) => {
+                *self.0.consumer.lock() = info.name;
+                Ok(())
+            }
+            Err(err) => {
+                self.0.consumer.lock().clear();
+                Err(err)
+            }
+        }
+    }
+
     /// Low level API that should be used with care.
     /// For standard use cases consider using [`PullSubscription::fetch`] or [`PullSubscription::fetch_with_handler`].
     /// Returns iterator for Current Subscription.
@@ -413,6 +568,7 @@ impl PullSubscription {
     ///     expires: Some(10000),
     ///     no_wait: true,
     ///     batch: 10,
+    ///     ..Default::default()
     /// })?;
     /// for (i, message) in consumer.iter().enumerate() {
     ///     println!("recieved message: {:?}", message);
@@ -426,6 +582,91 @@ impl PullSubscription {
         Iter { subscription: self }
     }
 
+    /// Returns an endless iterator over the messages of this `PullSubscription`, using the default
+    /// [`MessagesOptions`].
+    ///
+    /// The iterator keeps pulling messages in the background of each call, see
+    /// [`PullSubscription::messages_with_options`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # context.add_stream("next")?;
+    /// let consumer = context.pull_subscribe("next")?;
+    ///
+    /// for message in consumer.messages() {
+    ///     let message = message?;
+    ///     println!("received message: {:?}", message);
+    ///     message.ack()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn messages(&self) -> Messages {
+        self.messages_unchecked(MessagesOptions::default())
+    }
+
+    /// Returns an endless iterator over the messages of this `PullSubscription`.
+    ///
+    /// The iterator keeps up to `batch` messages and `max_bytes` bytes in flight, and sends a new
+    /// pull request whenever they fall below the configured thresholds. Expired requests, idle
+    /// heartbeats and leadership changes are handled internally. Missed heartbeats and other
+    /// unexpected statuses are yielded as errors wrapping a [`PullStatus`], after which the
+    /// iterator starts pulling again. The iterator only ends if the subscription is closed, the
+    /// consumer was deleted or the server rejected the pull request.
+    ///
+    /// An ordered consumer, see `PullSubscribeOptions::ordered`, is recreated instead whenever it
+    /// falls out of sequence, misses heartbeats or is deleted.
+    ///
+    /// Returns an `InvalidInput` error if `batch` is zero, since nothing would ever be pulled.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use nats::jetstream::MessagesOptions;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # context.add_stream("next")?;
+    /// let consumer = context.pull_subscribe("next")?;
+    ///
+    /// let messages = consumer.messages_with_options(MessagesOptions {
+    ///     batch: 100,
+    ///     max_bytes: 1024 * 1024,
+    ///     expires: Duration::from_secs(10),
+    ///     idle_heartbeat: Duration::from_secs(5),
+    ///     ..Default::default()
+    /// })?;
+    ///
+    /// for message in messages {
+    ///     message?.ack()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn messages_with_options(&self, options: MessagesOptions) -> io::Result<Messages> {
+        if options.batch == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the batch size must be greater than zero",
+            ));
+        }
+
+        Ok(self.messages_unchecked(options))
+    }
+
+    fn messages_unchecked(&self, options: MessagesOptions) -> Messages {
+        Messages(Arc::new(MessagesInner {
+            subscription: self.clone(),
+            options,
+            state: Mutex::new(MessagesState::default()),
+        }))
+    }
+
     /// utility to stop iterators if `no messages` or `request timeout` is encountered.
     fn preprocess(&self, message: Option<Message>) -> Option<Message> {
         if let Some(message) = message {
@@ -439,6 +680,171 @@ impl PullSubscription {
         }
         message
     }
+
+    /// Waits for the next message of a batch. Status messages are returned as errors wrapping a
+    /// `PullStatus`, as are idle heartbeats which did not arrive in time.
+    fn next_in_batch(
+        &self,
+        maybe_timeout: Option<Duration>,
+        idle_heartbeat: Duration,
+    ) -> io::Result<Message> {
+        let maybe_deadline = maybe_timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            // Heartbeats are considered missed after two intervals without any message.
+            let maybe_heartbeat_deadline = if idle_heartbeat.is_zero() {
+                None
+            } else {
+                Some(Instant::now() + idle_heartbeat * 2)
+            };
+
+            let wait_until = match (maybe_deadline, maybe_heartbeat_deadline) {
+                (Some(deadline), Some(heartbeat)) => Some(deadline.min(heartbeat)),
+                (deadline, heartbeat) => deadline.or(heartbeat),
+            };
+
+            let result = match wait_until {
+                Some(wait_until) => self.0.messages.recv_deadline(wait_until),
+                None => self
+                    .0
+                    .messages
+                    .recv()
+                    .map_err(|_| channel::RecvTimeoutError::Disconnected),
+            };
+
+            match result {
+                Ok(message) => {
+                    if message.is_idle_heartbeat() {
+                        continue;
+                    }
+
+                    if let Some(status) = PullStatus::from_message(&message) {
+                        return Err(status.into());
+                    }
+
+                    return Ok(message);
+                }
+                Err(channel::RecvTimeoutError::Timeout) => {
+                    if maybe_deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "next_timeout: timed out",
+                        ));
+                    }
+
+                    return Err(PullStatus::MissedHeartbeat.into());
+                }
+                Err(channel::RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "next_timeout: unsubscribed",
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// The reason a pull request ended before delivering the whole batch.
+///
+/// Returned by the batch iterators, and wrapped in an `io::Error` where errors are reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PullStatus {
+    /// `400`, the server rejected the request, with the description sent by the server.
+    BadRequest(String),
+    /// `404`, there were no messages to deliver for a `no_wait` request.
+    NoMessages,
+    /// `408`, the request expired.
+    RequestTimeout,
+    /// `409`, the consumer has reached its `max_ack_pending` limit.
+    MaxAckPending,
+    /// `409`, the consumer changed its leader and the request has to be sent again.
+    LeadershipChange,
+    /// `409`, the next message is larger than the requested `max_bytes`.
+    MessageSizeExceedsMaxBytes,
+    /// `409`, the consumer was deleted.
+    ConsumerDeleted,
+    /// Any other `409` status, with the description sent by the server.
+    Conflict(String),
+    /// The server did not send the requested idle heartbeats.
+    MissedHeartbeat,
+}
+
+impl PullStatus {
+    /// Returns the `PullStatus` wrapped by the given `io::Error`, if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&PullStatus> {
+        err.get_ref()
+            .and_then(|inner| inner.downcast_ref::<PullStatus>())
+    }
+
+    fn from_message(message: &Message) -> Option<PullStatus> {
+        let headers = message.headers.as_ref()?;
+        let description = headers
+            .get(header::DESCRIPTION)
+            .map(String::as_str)
+            .unwrap_or_default();
+
+        match headers.get(header::STATUS)?.as_str() {
+            "400" => Some(PullStatus::BadRequest(description.to_string())),
+            "404" => Some(PullStatus::NoMessages),
+            "408" => Some(PullStatus::RequestTimeout),
+            "409" => {
+                let description_lowercase = description.to_lowercase();
+                if description_lowercase.contains("max ack pending")
+                    || description_lowercase.contains("maxackpending")
+                {
+                    Some(PullStatus::MaxAckPending)
+                } else if description_lowercase.contains("leadership change") {
+                    Some(PullStatus::LeadershipChange)
+                } else if description_lowercase.contains("exceeds maxbytes") {
+                    Some(PullStatus::MessageSizeExceedsMaxBytes)
+                } else if description_lowercase.contains("consumer deleted") {
+                    Some(PullStatus::ConsumerDeleted)
+                } else {
+                    Some(PullStatus::Conflict(description.to_string()))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if the request simply ran out of messages or time.
+    fn is_end_of_batch(&self) -> bool {
+        matches!(self, PullStatus::NoMessages | PullStatus::RequestTimeout)
+    }
+}
+
+impl fmt::Display for PullStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PullStatus::BadRequest(description) => write!(f, "bad request: {}", description),
+            PullStatus::NoMessages => write!(f, "no messages"),
+            PullStatus::RequestTimeout => write!(f, "pull request timed out"),
+            PullStatus::MaxAckPending => write!(f, "exceeded max ack pending"),
+            PullStatus::LeadershipChange => write!(f, "consumer leadership changed"),
+            PullStatus::MessageSizeExceedsMaxBytes => write!(f, "message size exceeds max bytes"),
+            PullStatus::ConsumerDeleted => write!(f, "consumer deleted"),
+            PullStatus::Conflict(description) => {
+                write!(f, "pull request conflict: {}", description)
+            }
+            PullStatus::MissedHeartbeat => write!(f, "missed idle heartbeat"),
+        }
+    }
+}
+
+impl error::Error for PullStatus {}
+
+impl From<PullStatus> for io::Error {
+    fn from(status: PullStatus) -> io::Error {
+        let kind = match status {
+            PullStatus::NoMessages => io::ErrorKind::NotFound,
+            PullStatus::RequestTimeout | PullStatus::MissedHeartbeat => io::ErrorKind::TimedOut,
+            PullStatus::BadRequest(_) => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::Other,
+        };
+
+        io::Error::new(kind, status)
+    }
 }
 
 /// Interator that will endlessly wait for messages, unless `no messages` or `request timeout` is encountered.
@@ -470,17 +876,33 @@ impl<'a> Iterator for TimeoutIter<'a> {
 pub struct BatchIter<'a> {
     batch_size: usize,
     processed: usize,
+    idle_heartbeat: Duration,
+    status: Option<PullStatus>,
     subscription: &'a PullSubscription,
 }
 
+impl<'a> BatchIter<'a> {
+    /// Returns the reason the batch ended early, if any.
+    pub fn status(&self) -> Option<&PullStatus> {
+        self.status.as_ref()
+    }
+}
+
 impl<'a> Iterator for BatchIter<'a> {
     type Item = Message;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.processed >= self.batch_size {
-            None
-        } else {
-            self.processed += 1;
-            self.subscription.next()
+        if self.processed >= self.batch_size || self.status.is_some() {
+            return None;
+        }
+
+        self.processed += 1;
+        match self.subscription.next_in_batch(None, self.idle_heartbeat) {
+            Ok(message) => Some(message),
+            Err(err) => {
+                self.status = PullStatus::from_io_error(&err).cloned();
+                self.processed = self.batch_size;
+                None
+            }
         }
     }
 }
@@ -515,18 +937,289 @@ pub struct TimeoutBatchIter<'a> {
     batch_size: usize,
     processed: usize,
     timeout: Duration,
+    idle_heartbeat: Duration,
+    status: Option<PullStatus>,
     subscription: &'a PullSubscription,
 }
 
+impl<'a> TimeoutBatchIter<'a> {
+    /// Returns the reason the batch ended early, if any.
+    pub fn status(&self) -> Option<&PullStatus> {
+        self.status.as_ref()
+    }
+}
+
 impl<'a> Iterator for TimeoutBatchIter<'a> {
     type Item = io::Result<Message>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.processed >= self.batch_size {
-            None
+        if self.processed >= self.batch_size || self.status.is_some() {
+            return None;
+        }
+
+        self.processed += 1;
+        match self
+            .subscription
+            .next_in_batch(Some(self.timeout), self.idle_heartbeat)
+        {
+            Ok(message) => Some(Ok(message)),
+            Err(err) => match PullStatus::from_io_error(&err).cloned() {
+                Some(status) => {
+                    let end_of_batch = status.is_end_of_batch();
+                    self.status = Some(status);
+                    if end_of_batch {
+                        None
+                    } else {
+                        Some(Err(err))
+                    }
+                }
+                None => Some(Err(err)),
+            },
+        }
+    }
+}
+
+/// Endless iterator over the messages of a `PullSubscription`, see
+/// [`PullSubscription::messages_with_options`].
+pub struct Messages(Arc<MessagesInner>);
+
+impl Messages {
+    /// Waits for the next message without blocking the async executor.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # smol::block_on(async {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// let consumer = context.pull_subscribe("next")?;
+    /// let messages = consumer.messages();
+    ///
+    /// while let Some(message) = messages.next_async().await {
+    ///     message?.ack()?;
+    /// }
+    /// # std::io::Result::Ok(())
+    /// # });
+    /// ```
+    pub async fn next_async(&self) -> Option<io::Result<Message>> {
+        let inner = self.0.clone();
+        blocking::unblock(move || inner.next()).await
+    }
+}
+
+impl Iterator for Messages {
+    type Item = io::Result<Message>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+struct MessagesInner {
+    subscription: PullSubscription,
+    options: MessagesOptions,
+    state: Mutex<MessagesState>,
+}
+
+#[derive(Default)]
+struct MessagesState {
+    /// Number of messages requested but not delivered yet.
+    pending_messages: usize,
+    /// Number of bytes requested but not delivered yet.
+    pending_bytes: usize,
+    /// Set once the iterator cannot continue.
+    done: bool,
+    /// Set when an ordered consumer fell out of sequence and has to be recreated.
+    recreate: bool,
+}
+
+impl MessagesState {
+    fn reset(&mut self) {
+        self.pending_messages = 0;
+        self.pending_bytes = 0;
+    }
+
+    /// Accounts for a request which ended, using the pending counts reported by the server.
+    fn request_ended(&mut self, message: &Message) {
+        let pending = |name| {
+            message
+                .headers
+                .as_ref()
+                .and_then(|headers| headers.get(name))
+                .and_then(|value| value.parse::<usize>().ok())
+        };
+
+        match (
+            pending(header::NATS_PENDING_MESSAGES),
+            pending(header::NATS_PENDING_BYTES),
+        ) {
+            (Some(messages), bytes) => {
+                self.pending_messages = self.pending_messages.saturating_sub(messages);
+                self.pending_bytes = self.pending_bytes.saturating_sub(bytes.unwrap_or(0));
+            }
+            // Older servers do not report what was left, so start over.
+            (None, _) => self.reset(),
+        }
+    }
+}
+
+impl MessagesInner {
+    fn next(&self) -> Option<io::Result<Message>> {
+        let options = &self.options;
+        let maybe_ordered = self.subscription.0.ordered.as_ref();
+        let mut state = self.state.lock();
+
+        loop {
+            if state.done {
+                return None;
+            }
+
+            if let Some(ordered) = maybe_ordered.filter(|_| state.recreate) {
+                if let Err(err) = self.subscription.recreate_ordered(ordered) {
+                    return Some(Err(err));
+                }
+                state.recreate = false;
+                state.reset();
+            }
+
+            if let Err(err) = self.pull_if_needed(&mut state) {
+                return Some(Err(err));
+            }
+
+            // Heartbeats are considered missed after two intervals without any message.
+            let result = if !options.idle_heartbeat.is_zero() {
+                self.subscription
+                    .0
+                    .messages
+                    .recv_timeout(options.idle_heartbeat * 2)
+            } else if !options.expires.is_zero() {
+                self.subscription
+                    .0
+                    .messages
+                    .recv_timeout(options.expires + Duration::from_secs(1))
+            } else {
+                self.subscription
+                    .0
+                    .messages
+                    .recv()
+                    .map_err(|_| channel::RecvTimeoutError::Disconnected)
+            };
+
+            let message = match result {
+                Ok(message) => message,
+                Err(channel::RecvTimeoutError::Timeout) => {
+                    // Any outstanding request is considered lost.
+                    state.reset();
+                    if options.idle_heartbeat.is_zero() {
+                        continue;
+                    }
+
+                    // The ordered consumer itself may be gone, start over with a new one.
+                    if maybe_ordered.is_some() {
+                        state.recreate = true;
+                        continue;
+                    }
+
+                    return Some(Err(PullStatus::MissedHeartbeat.into()));
+                }
+                Err(channel::RecvTimeoutError::Disconnected) => {
+                    state.done = true;
+                    return None;
+                }
+            };
+
+            if message.is_idle_heartbeat() {
+                continue;
+            }
+
+            match PullStatus::from_message(&message) {
+                None => {
+                    if let Some(ordered) = maybe_ordered {
+                        if !ordered.track(&message) {
+                            state.recreate = true;
+                            continue;
+                        }
+                    }
+
+                    state.pending_messages = state.pending_messages.saturating_sub(1);
+                    state.pending_bytes = state.pending_bytes.saturating_sub(
+                        message.subject.len()
+                            + message.reply.as_ref().map_or(0, String::len)
+                            + message
+                                .headers
+                                .as_ref()
+                                .map_or(0, |headers| headers.to_bytes().len())
+                            + message.data.len(),
+                    );
+
+                    return Some(Ok(message));
+                }
+                Some(PullStatus::NoMessages)
+                | Some(PullStatus::RequestTimeout)
+                | Some(PullStatus::MaxAckPending) => {
+                    state.request_ended(&message);
+                }
+                Some(PullStatus::LeadershipChange) => {
+                    state.reset();
+                }
+                Some(PullStatus::ConsumerDeleted) if maybe_ordered.is_some() => {
+                    state.recreate = true;
+                }
+                Some(status @ PullStatus::ConsumerDeleted)
+                | Some(status @ PullStatus::BadRequest(_))
+                | Some(status @ PullStatus::MessageSizeExceedsMaxBytes) => {
+                    state.done = true;
+                    return Some(Err(status.into()));
+                }
+                Some(status) => {
+                    state.request_ended(&message);
+                    return Some(Err(status.into()));
+                }
+            }
+        }
+    }
+
+    /// Sends a new pull request if the messages or bytes in flight fell below the thresholds.
+    fn pull_if_needed(&self, state: &mut MessagesState) -> io::Result<()> {
+        let options = &self.options;
+
+        let threshold_messages = if options.threshold_messages == 0 {
+            options.batch / 2
         } else {
-            self.processed += 1;
-            Some(self.subscription.next_timeout(self.timeout))
+            options.threshold_messages
+        };
+        let threshold_bytes = if options.threshold_bytes == 0 {
+            options.max_bytes / 2
+        } else {
+            options.threshold_bytes
+        };
+
+        let below_messages = state.pending_messages <= threshold_messages;
+        let below_bytes = options.max_bytes > 0 && state.pending_bytes <= threshold_bytes;
+        let batch = options.batch.saturating_sub(state.pending_messages);
+
+        if !(below_messages || below_bytes) || batch == 0 {
+            return Ok(());
         }
+
+        let max_bytes = options.max_bytes.saturating_sub(state.pending_bytes);
+        if options.max_bytes > 0 && max_bytes == 0 {
+            return Ok(());
+        }
+
+        self.subscription.request_batch(BatchOptions {
+            batch,
+            expires: if options.expires.is_zero() {
+                None
+            } else {
+                Some(options.expires.as_nanos() as usize)
+            },
+            no_wait: false,
+            max_bytes,
+            idle_heartbeat: options.idle_heartbeat,
+        })?;
+
+        state.pending_messages += batch;
+        state.pending_bytes += max_bytes;
+
+        Ok(())
     }
 }
 
@@ -534,8 +1227,7 @@ impl From<usize> for BatchOptions {
     fn from(batch: usize) -> Self {
         BatchOptions {
             batch,
-            expires: None,
-            no_wait: false,
+            ..Default::default()
         }
     }
 }
@@ -14,12 +14,17 @@
 use std::io;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use crossbeam_channel as channel;
+use parking_lot::Mutex;
 
-use crate::jetstream::{AckPolicy, ConsumerInfo, ConsumerOwnership, JetStream};
+use crate::header;
+use crate::jetstream::{
+    AckPolicy, ConsumerConfig, ConsumerInfo, ConsumerOwnership, DeliverPolicy, JetStream,
+    SequencePair,
+};
 use crate::message::Message;
 use crate::DEFAULT_FLUSH_TIMEOUT;
 
@@ -34,8 +39,9 @@ pub(crate) struct Inner {
     /// Name of the stream associated with the subscription.
     pub(crate) stream: String,
 
-    /// Name of the consumer associated with the subscription.
-    pub(crate) consumer: String,
+    /// Name of the consumer associated with the subscription, which changes when an ordered
+    /// consumer is recreated.
+    pub(crate) consumer: Arc<Mutex<String>>,
 
     /// Ack policy used in while processing messages.
     pub(crate) consumer_ack_policy: AckPolicy,
@@ -43,12 +49,21 @@ pub(crate) struct Inner {
     /// Indicates if we own the consumer and are responsible for deleting it or not.
     pub(crate) consumer_ownership: ConsumerOwnership,
 
+    /// Interval of in-progress acknowledgements sent while handlers run, if any.
+    pub(crate) auto_progress: Option<Duration>,
+
+    /// Monitor keeping an ordered consumer in sequence, if the consumer is ordered.
+    pub(crate) ordered_monitor: Mutex<Option<OrderedMonitor>>,
+
     /// Client associated with subscription.
     pub(crate) context: JetStream,
 }
 
 impl Drop for Inner {
     fn drop(&mut self) {
+        // Stop recreating the consumer before it gets deleted.
+        self.ordered_monitor.lock().take();
+
         self.context
             .connection
             .0
@@ -59,12 +74,19 @@ impl Drop for Inner {
         // Delete the consumer, if we own it.
         if self.consumer_ownership == ConsumerOwnership::Yes {
             self.context
-                .delete_consumer(&self.stream, &self.consumer)
+                .delete_consumer(&self.stream, self.consumer())
                 .ok();
         }
     }
 }
 
+impl Inner {
+    /// Returns the name of the current consumer.
+    pub(crate) fn consumer(&self) -> String {
+        self.consumer.lock().clone()
+    }
+}
+
 /// A `PushSubscription` receives `Message`s published
 /// to specific NATS `Subject`s.
 #[derive(Clone, Debug)]
@@ -74,18 +96,24 @@ impl PushSubscription {
     /// Creates a subscription.
     pub(crate) fn new(
         sid: Arc<AtomicU64>,
+        consumer: Arc<Mutex<String>>,
         consumer_info: ConsumerInfo,
         consumer_ownership: ConsumerOwnership,
         messages: channel::Receiver<Message>,
+        auto_progress: Option<Duration>,
         context: JetStream,
     ) -> PushSubscription {
+        *consumer.lock() = consumer_info.name;
+
         PushSubscription(Arc::new(Inner {
             sid,
             stream: consumer_info.stream_name,
-            consumer: consumer_info.name,
+            consumer,
             consumer_ack_policy: consumer_info.config.ack_policy,
             consumer_ownership,
             messages,
+            auto_progress,
+            ordered_monitor: Mutex::new(None),
             context,
         }))
     }
@@ -327,7 +355,8 @@ impl PushSubscription {
         thread::Builder::new()
             .name(format!(
                 "nats_jetstream_push_subscriber_{}_{}",
-                self.0.stream, self.0.consumer,
+                self.0.stream,
+                self.0.consumer(),
             ))
             .spawn(move || {
                 for m in sub.iter() {
@@ -370,6 +399,7 @@ impl PushSubscription {
         F: Fn(&Message) -> io::Result<()> + Send + 'static,
     {
         let consumer_ack_policy = self.0.consumer_ack_policy;
+        let auto_progress = self.0.auto_progress;
 
         // This will allow us to not have to capture the return. When it is
         // dropped it will not unsubscribe from the server.
@@ -377,13 +407,16 @@ impl PushSubscription {
         thread::Builder::new()
             .name(format!(
                 "nats_push_subscriber_{}_{}",
-                self.0.consumer, self.0.stream
+                self.0.consumer(),
+                self.0.stream
             ))
             .spawn(move || {
                 for message in sub.iter() {
+                    let progress = auto_progress.map(|interval| message.auto_progress(interval));
                     if let Err(err) = handler(&message) {
                         log::error!("Error in callback! {:?}", err);
                     }
+                    drop(progress);
 
                     if consumer_ack_policy != AckPolicy::None {
                         if let Err(err) = message.ack() {
@@ -424,7 +457,12 @@ impl PushSubscription {
     pub fn process<R, F: Fn(&Message) -> io::Result<R>>(&mut self, f: F) -> io::Result<R> {
         let next = self.next().unwrap();
 
+        let progress = self
+            .0
+            .auto_progress
+            .map(|interval| next.auto_progress(interval));
         let result = f(&next)?;
+        drop(progress);
         if self.0.consumer_ack_policy != AckPolicy::None {
             next.ack()?;
         }
@@ -463,7 +501,12 @@ impl PushSubscription {
     ) -> io::Result<R> {
         let next = self.next_timeout(timeout)?;
 
+        let progress = self
+            .0
+            .auto_progress
+            .map(|interval| next.auto_progress(interval));
         let ret = f(&next)?;
+        drop(progress);
         if self.0.consumer_ack_policy != AckPolicy::None {
             next.ack()?;
         }
@@ -489,7 +532,7 @@ impl PushSubscription {
     pub fn consumer_info(&self) -> io::Result<ConsumerInfo> {
         self.0
             .context
-            .consumer_info(&self.0.stream, &self.0.consumer)
+            .consumer_info(&self.0.stream, self.0.consumer())
     }
 
     /// Unsubscribe a subscription immediately without draining.
@@ -525,7 +568,7 @@ impl PushSubscription {
         if self.0.consumer_ownership == ConsumerOwnership::Yes {
             self.0
                 .context
-                .delete_consumer(&self.0.stream, &self.0.consumer)
+                .delete_consumer(&self.0.stream, self.0.consumer())
                 .ok();
         }
 
@@ -610,7 +653,7 @@ impl PushSubscription {
         if self.0.consumer_ownership == ConsumerOwnership::Yes {
             self.0
                 .context
-                .delete_consumer(&self.0.stream, &self.0.consumer)
+                .delete_consumer(&self.0.stream, self.0.consumer())
                 .ok();
         }
 
@@ -711,3 +754,217 @@ impl<'a> Iterator for TimeoutIter<'a> {
         self.subscription.next_timeout(self.to).ok()
     }
 }
+
+/// Keeps an ordered consumer in sequence. Whenever a gap in the consumer sequence is detected,
+/// heartbeats are missed or the client reconnects, the consumer is deleted and recreated starting
+/// right after the last stream sequence that was delivered.
+pub(crate) struct OrderedConsumer {
+    context: JetStream,
+    stream: String,
+    config: ConsumerConfig,
+    sid: Arc<AtomicU64>,
+    consumer: Arc<Mutex<String>>,
+    sequence_pair: Mutex<SequencePair>,
+    last_active: Mutex<Instant>,
+    // Recreations requested while preprocessing, carried out by the monitor thread.
+    resets: channel::Sender<(u64, u64)>,
+    pending_resets: channel::Receiver<(u64, u64)>,
+}
+
+/// Stops the monitor thread of an ordered consumer when dropped.
+#[derive(Debug)]
+pub(crate) struct OrderedMonitor {
+    stop: Option<channel::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for OrderedMonitor {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the thread, wait for it so the consumer is not recreated
+        // after the subscription is gone.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl OrderedConsumer {
+    pub(crate) fn new(
+        context: JetStream,
+        stream: String,
+        config: ConsumerConfig,
+        sid: Arc<AtomicU64>,
+        consumer: Arc<Mutex<String>>,
+    ) -> OrderedConsumer {
+        let (resets, pending_resets) = channel::unbounded();
+
+        OrderedConsumer {
+            context,
+            stream,
+            config,
+            sid,
+            consumer,
+            sequence_pair: Mutex::new(SequencePair {
+                consumer_seq: 0,
+                stream_seq: 0,
+            }),
+            last_active: Mutex::new(Instant::now()),
+            resets,
+            pending_resets,
+        }
+    }
+
+    /// Tracks sequences of incoming messages.
+    /// Returns true if the message should be filtered out from the user's view.
+    pub(crate) fn preprocess(self: &Arc<Self>, sid: u64, message: &Message) -> bool {
+        // Drop anything still in flight for a consumer which was replaced.
+        if sid != self.sid.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        *self.last_active.lock() = Instant::now();
+
+        if message.is_flow_control() {
+            return false;
+        }
+
+        if message.is_idle_heartbeat() {
+            let maybe_consumer_seq = message
+                .headers
+                .as_ref()
+                .and_then(|headers| headers.get(header::NATS_LAST_CONSUMER))
+                .and_then(|consumer_seq| consumer_seq.parse::<u64>().ok());
+
+            if let Some(consumer_seq) = maybe_consumer_seq {
+                if consumer_seq != self.sequence_pair.lock().consumer_seq {
+                    return self.reset(sid);
+                }
+            }
+
+            return false;
+        }
+
+        if let Some(message_info) = message.jetstream_message_info() {
+            let mut sequence_pair = self.sequence_pair.lock();
+            if message_info.consumer_seq != sequence_pair.consumer_seq + 1 {
+                drop(sequence_pair);
+                return self.reset(sid);
+            }
+
+            sequence_pair.stream_seq = message_info.stream_seq;
+            sequence_pair.consumer_seq = message_info.consumer_seq;
+        }
+
+        false
+    }
+
+    /// Spawns a thread which recreates the consumer when a reset was requested, heartbeats are
+    /// missed or the client reconnects. The thread stops once the returned monitor is dropped.
+    pub(crate) fn monitor(self: &Arc<Self>) -> OrderedMonitor {
+        let ordered = self.clone();
+        let interval = self.config.idle_heartbeat;
+        let (stop, stopped) = channel::bounded::<()>(1);
+
+        let thread = thread::Builder::new()
+            .name(format!("nats_ordered_consumer_monitor_{}", self.stream))
+            .spawn(move || {
+                let state = &ordered.context.connection.0.client.state;
+                let mut reconnects = state.reconnects.load(Ordering::Relaxed);
+
+                loop {
+                    channel::select! {
+                        recv(stopped) -> _ => return,
+                        recv(ordered.pending_resets) -> reset => {
+                            if let Ok((sid, stream_seq)) = reset {
+                                ordered.recreate(sid, stream_seq);
+                            }
+                        }
+                        default(interval) => {
+                            let current_reconnects = state.reconnects.load(Ordering::Relaxed);
+                            let missed_heartbeat =
+                                ordered.last_active.lock().elapsed() > interval * 2;
+
+                            if current_reconnects != reconnects || missed_heartbeat {
+                                reconnects = current_reconnects;
+                                ordered.reset(ordered.sid.load(Ordering::Relaxed));
+                            }
+                        }
+                    }
+                }
+            })
+            .expect("threads should be spawnable");
+
+        OrderedMonitor {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Mutes the current subscription and has the monitor thread recreate the consumer.
+    fn reset(&self, sid: u64) -> bool {
+        // Immediately mute the subscription so that messages no longer get delivered to it.
+        //
+        // If we are already muted then the triggering slipped through and we can
+        // return early as a resubscription will already be on the way.
+        if !self.context.connection.0.client.mute(sid).unwrap_or(false) {
+            return true;
+        }
+
+        let stream_seq = {
+            let mut sequence_pair = self.sequence_pair.lock();
+            sequence_pair.consumer_seq = 0;
+            sequence_pair.stream_seq
+        };
+
+        *self.last_active.lock() = Instant::now();
+
+        self.resets.send((sid, stream_seq)).ok();
+
+        true
+    }
+
+    fn recreate(&self, sid: u64, stream_seq: u64) {
+        let old_consumer = self.consumer.lock().clone();
+        if !old_consumer.is_empty() {
+            self.context
+                .delete_consumer(&self.stream, old_consumer)
+                .ok();
+        }
+
+        let deliver_subject = self.context.connection.new_inbox();
+        let new_sid = match self
+            .context
+            .connection
+            .0
+            .client
+            .resubscribe(sid, &deliver_subject)
+        {
+            Ok(new_sid) => new_sid,
+            Err(err) => {
+                log::error!("failed to resubscribe ordered consumer: {}", err);
+                return;
+            }
+        };
+
+        self.sid.store(new_sid, Ordering::Relaxed);
+
+        // Continue right after the last delivered message, or start over if nothing was
+        // delivered yet.
+        let mut config = self.config.clone();
+        config.deliver_subject = Some(deliver_subject);
+        if stream_seq > 0 {
+            config.deliver_policy = DeliverPolicy::ByStartSeq;
+            config.opt_start_seq = Some(stream_seq + 1);
+            config.opt_start_time = None;
+        }
+
+        match self.context.add_consumer(&self.stream, config) {
+            Ok(info) => *self.consumer.lock() = info.name,
+            Err(err) => {
+                self.consumer.lock().clear();
+                log::error!("failed to recreate ordered consumer: {}", err);
+            }
+        }
+    }
+}
@@ -11,24 +11,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::time::Duration;
 
-use crate::header::HeaderMap;
+use crate::header::{self, HeaderMap};
+use crate::Message;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::io::{self, ErrorKind};
+use time::format_description::well_known::Rfc3339;
 use time::serde::rfc3339;
 
 /// A UTC time
 pub type DateTime = time::OffsetDateTime;
 
-#[derive(Serialize)]
+#[derive(Default, Serialize)]
 pub(crate) struct StreamMessageGetRequest {
     #[serde(default, skip_serializing_if = "is_default")]
     pub seq: Option<u64>,
 
     #[serde(default, rename = "last_by_subj", skip_serializing_if = "is_default")]
     pub last_by_subject: Option<String>,
+
+    #[serde(default, rename = "next_by_subj", skip_serializing_if = "is_default")]
+    pub next_by_subject: Option<String>,
 }
 
 /// A raw stream message in the representation it is stored.
@@ -111,6 +117,49 @@ impl TryFrom<RawStreamMessage> for StreamMessage {
     }
 }
 
+impl TryFrom<Message> for StreamMessage {
+    type Error = io::Error;
+
+    /// Converts a reply from the direct get API, which carries the message metadata in headers.
+    fn try_from(message: Message) -> Result<StreamMessage, Self::Error> {
+        let headers = message.headers.ok_or_else(|| {
+            io::Error::new(ErrorKind::Other, "direct get response is missing headers")
+        })?;
+
+        let subject = headers
+            .get(header::NATS_SUBJECT)
+            .ok_or_else(|| {
+                io::Error::new(ErrorKind::Other, "direct get response is missing subject")
+            })?
+            .to_string();
+
+        let sequence = headers
+            .get(header::NATS_SEQUENCE)
+            .ok_or_else(|| {
+                io::Error::new(ErrorKind::Other, "direct get response is missing sequence")
+            })?
+            .parse::<u64>()
+            .map_err(|err| io::Error::new(ErrorKind::Other, err))?;
+
+        let time = headers
+            .get(header::NATS_TIME_STAMP)
+            .ok_or_else(|| {
+                io::Error::new(ErrorKind::Other, "direct get response is missing timestamp")
+            })
+            .and_then(|time| {
+                DateTime::parse(time, &Rfc3339).map_err(|err| io::Error::new(ErrorKind::Other, err))
+            })?;
+
+        Ok(StreamMessage {
+            subject,
+            sequence,
+            headers: Some(headers),
+            data: message.data,
+            time,
+        })
+    }
+}
+
 #[derive(Serialize)]
 pub(crate) struct DeleteRequest {
     pub seq: u64,
@@ -121,6 +170,41 @@ pub(crate) struct DeleteResponse {
     pub success: bool,
 }
 
+#[derive(Deserialize)]
+pub(crate) struct SuccessResponse {
+    pub success: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct PeerRemoveRequest {
+    pub peer: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct StreamSnapshotRequest {
+    /// The subject the snapshot chunks are delivered to.
+    pub deliver_subject: String,
+    /// Do not include consumers in the snapshot.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub no_consumers: bool,
+    /// Optimal chunk size for the snapshot data.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub chunk_size: usize,
+    /// Check all message checksums before taking the snapshot.
+    #[serde(default, rename = "jsck", skip_serializing_if = "is_default")]
+    pub check_msgs: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct StreamRestoreRequest {
+    pub config: StreamConfig,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct StreamRestoreResponse {
+    pub deliver_subject: String,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub(crate) struct CreateConsumerRequest {
     pub stream_name: String,
@@ -218,6 +302,10 @@ pub struct ConsumerConfig {
     /// When consuming from a Stream with many subjects, or wildcards, this selects only specific incoming subjects. Supports wildcards.
     #[serde(default, skip_serializing_if = "is_default")]
     pub filter_subject: String,
+    /// Selects only messages whose subject matches any of these subjects. Cannot be combined with
+    /// `filter_subject`. Requires nats-server 2.10 or newer.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub filter_subjects: Vec<String>,
     /// Whether messages are sent as quickly as possible or at the rate of receipt
     pub replay_policy: ReplayPolicy,
     /// The rate of message delivery in bits per second
@@ -252,6 +340,24 @@ pub struct ConsumerConfig {
     /// Threshold for ephemeral consumer intactivity
     #[serde(default, with = "serde_nanos", skip_serializing_if = "is_default")]
     pub inactive_threshold: Duration,
+    /// Delays between redeliveries of a message which was not acknowledged, overriding `ack_wait`.
+    /// The last delay is used for any further redeliveries. `max_deliver` must be larger than the
+    /// number of delays.
+    #[serde(default, with = "serde_nanos", skip_serializing_if = "is_default")]
+    pub backoff: Vec<Duration>,
+    /// Number of replicas for the consumer's state. Defaults to the replicas of the stream.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub num_replicas: usize,
+    /// Keep the consumer's state in memory instead of inheriting the storage type of the stream.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub mem_storage: bool,
+    /// Additional user defined metadata attached to the consumer.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub metadata: HashMap<String, String>,
+    /// Pauses delivery of messages to the consumer until this time. Requires nats-server 2.11 or
+    /// newer.
+    #[serde(default, skip_serializing_if = "is_default", with = "rfc3339::option")]
+    pub pause_until: Option<DateTime>,
 }
 
 pub(crate) enum ConsumerKind {
@@ -359,6 +465,73 @@ pub struct StreamConfig {
     /// Indicates if purges will be denied or not.
     #[serde(default, skip_serializing_if = "is_default")]
     pub deny_purge: bool,
+    /// Allow any replica of the stream to answer direct get requests.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub allow_direct: bool,
+    /// Allow the stream to answer direct get requests for the stream it mirrors.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub mirror_direct: bool,
+    /// Makes the stream a read-only replica of another stream, possibly in another domain.
+    /// Mirrors cannot have subjects.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub mirror: Option<Source>,
+    /// Streams, possibly in other domains, whose messages are copied into this stream.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub sources: Vec<Source>,
+}
+
+/// A stream mirrored or sourced by another stream.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Source {
+    /// The name of the stream
+    pub name: String,
+    /// Start copying at this sequence
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub opt_start_seq: Option<u64>,
+    /// Start copying messages received after this time
+    #[serde(default, skip_serializing_if = "is_default", with = "rfc3339::option")]
+    pub opt_start_time: Option<DateTime>,
+    /// Only copy messages matching this subject
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub filter_subject: Option<String>,
+    /// Rewrites the subjects of copied messages. Requires nats-server 2.10 or newer.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub subject_transforms: Vec<SubjectTransform>,
+    /// Where to find the stream if it lives in another account or domain
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub external: Option<External>,
+}
+
+/// The location of a stream in another account or `JetStream` domain.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct External {
+    /// The API prefix of the remote `JetStream` API, such as `$JS.hub.API`
+    #[serde(rename = "api")]
+    pub api_prefix: String,
+    /// The prefix of the subject messages are delivered on
+    #[serde(default, rename = "deliver", skip_serializing_if = "is_default")]
+    pub delivery_prefix: Option<String>,
+}
+
+impl External {
+    /// Returns the location of streams in the given `JetStream` domain.
+    pub fn domain(domain: &str) -> External {
+        External {
+            api_prefix: format!("$JS.{}.API", domain),
+            ..Default::default()
+        }
+    }
+}
+
+/// Maps the subjects of messages matching `source` to `destination`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SubjectTransform {
+    /// The subject to match, may contain wildcards
+    #[serde(rename = "src")]
+    pub source: String,
+    /// The subject to map to, may reference wildcards of the source
+    #[serde(rename = "dest")]
+    pub destination: String,
 }
 
 fn is_default<T: Default + Eq>(t: &T) -> bool {
@@ -390,6 +563,9 @@ pub struct StreamInfo {
     pub created: DateTime,
     /// Various metrics associated with this stream
     pub state: StreamState,
+    /// Information about the stream's cluster
+    #[serde(default)]
+    pub cluster: ClusterInfo,
 }
 
 /// Information about a received message
@@ -417,6 +593,15 @@ pub struct JetStreamMessageInfo<'a> {
     pub token: Option<&'a str>,
 }
 
+/// Describes the stream captured by a snapshot.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamSnapshotInfo {
+    /// The configuration of the stream at the time of the snapshot
+    pub config: StreamConfig,
+    /// The state of the stream at the time of the snapshot
+    pub state: StreamState,
+}
+
 /// information about the given stream.
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct StreamState {
@@ -529,7 +714,7 @@ pub struct PurgeRequest {
     pub filter: Option<String>,
 
     /// Number of messages to keep.
-    #[serde(default, rename = "filter", skip_serializing_if = "is_default")]
+    #[serde(default, rename = "keep", skip_serializing_if = "is_default")]
     pub keep: Option<u64>,
 }
 
@@ -612,6 +797,18 @@ pub struct AccountLimits {
     pub max_streams: i64,
     /// Maximum consumers for this account (-1 if no limit)
     pub max_consumers: i64,
+    /// Maximum number of unacknowledged messages per consumer (-1 if no limit)
+    #[serde(default)]
+    pub max_ack_pending: i64,
+    /// Maximum bytes a single memory stream may reserve (0 if no limit)
+    #[serde(default)]
+    pub memory_max_stream_bytes: i64,
+    /// Maximum bytes a single file stream may reserve (0 if no limit)
+    #[serde(default)]
+    pub storage_max_stream_bytes: i64,
+    /// Whether streams must set `max_bytes`
+    #[serde(default)]
+    pub max_bytes_required: bool,
 }
 
 /// returns current statistics about the account's `JetStream` usage.
@@ -702,17 +899,71 @@ pub struct ConsumerInfo {
     /// The number of pending
     pub num_pending: u64,
     /// Information about the consumer's cluster
+    #[serde(default)]
     pub cluster: ClusterInfo,
     /// Indicates if any client is connected and receiving messages from a push consumer
     #[serde(default)]
     pub push_bound: bool,
+    /// Indicates if the consumer is currently paused
+    #[serde(default)]
+    pub paused: bool,
+    /// How long the consumer remains paused
+    #[serde(default, with = "serde_nanos")]
+    pub pause_remaining: Duration,
+}
+
+/// The pause state of a consumer after pausing or resuming it.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PauseResponse {
+    /// Whether the consumer is paused
+    #[serde(default)]
+    pub paused: bool,
+    /// The time until which the consumer is paused
+    #[serde(default, skip_serializing_if = "is_default", with = "rfc3339::option")]
+    pub pause_until: Option<DateTime>,
+    /// How long the consumer remains paused
+    #[serde(default, with = "serde_nanos")]
+    pub pause_remaining: Duration,
+}
+
+/// The request to pause or resume a consumer.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub(crate) struct ConsumerPauseRequest {
+    #[serde(default, skip_serializing_if = "is_default", with = "rfc3339::option")]
+    pub pause_until: Option<DateTime>,
 }
 
 /// Information about the consumer's associated `JetStream` cluster
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct ClusterInfo {
+    /// The name of the cluster
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub name: Option<String>,
     /// The leader of the cluster
+    #[serde(default)]
     pub leader: String,
+    /// The other members of the raft group
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub replicas: Vec<PeerInfo>,
+}
+
+/// Information about a member of a `JetStream` raft group
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    /// The server name of the peer
+    pub name: String,
+    /// Indicates if the peer is up to date with the leader
+    #[serde(default)]
+    pub current: bool,
+    /// Indicates if the peer is considered offline
+    #[serde(default)]
+    pub offline: bool,
+    /// Time since this peer was last seen
+    #[serde(default, with = "serde_nanos")]
+    pub active: Duration,
+    /// How many operations this peer is behind the leader
+    #[serde(default)]
+    pub lag: u64,
 }
 
 /// Information about a consumer and the stream it is consuming
@@ -739,6 +990,47 @@ pub struct BatchOptions {
     /// Consumer has reached MaxAckPending limits.
     #[serde(default, skip_serializing_if = "is_default")]
     pub no_wait: bool,
+    /// The maximum number of bytes of all messages delivered for this request. The server ends
+    /// the request once the limit is reached. Zero means no limit.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub max_bytes: usize,
+    /// If set, the server sends idle heartbeats while this request is pending so the client can
+    /// detect when the server went away.
+    #[serde(default, with = "serde_nanos", skip_serializing_if = "is_default")]
+    pub idle_heartbeat: Duration,
+}
+
+/// Options for the continuous iterator returned by `PullSubscription::messages_with_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessagesOptions {
+    /// The maximum number of messages kept in flight.
+    pub batch: usize,
+    /// The maximum number of bytes kept in flight. Zero means no limit.
+    pub max_bytes: usize,
+    /// A new pull request is sent once the number of messages in flight falls to this value.
+    /// Zero means half of `batch`.
+    pub threshold_messages: usize,
+    /// A new pull request is sent once the number of bytes in flight falls to this value.
+    /// Zero means half of `max_bytes`.
+    pub threshold_bytes: usize,
+    /// How long each pull request is kept by the server before it expires.
+    pub expires: Duration,
+    /// How often the server sends heartbeats while a pull request is pending. Must be at most
+    /// half of `expires`, zero disables heartbeats.
+    pub idle_heartbeat: Duration,
+}
+
+impl Default for MessagesOptions {
+    fn default() -> MessagesOptions {
+        MessagesOptions {
+            batch: 500,
+            max_bytes: 0,
+            threshold_messages: 0,
+            threshold_bytes: 0,
+            expires: Duration::from_secs(30),
+            idle_heartbeat: Duration::from_secs(15),
+        }
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -760,6 +1052,8 @@ pub struct PullSubscribeOptions {
     pub(crate) durable_name: Option<String>,
     pub(crate) bind_only: bool,
     pub(crate) consumer_config: Option<ConsumerConfig>,
+    pub(crate) auto_progress: Option<Duration>,
+    pub(crate) ordered: bool,
 }
 
 impl PullSubscribeOptions {
@@ -789,6 +1083,26 @@ impl PullSubscribeOptions {
         self.durable_name = Some(consumer_name);
         self
     }
+
+    /// Sends in-progress acknowledgements at the given interval while a message is processed by
+    /// `fetch_with_handler`, so long running handlers do not exceed the consumer's `ack_wait`.
+    /// Intervals shorter than 100 milliseconds are raised to it.
+    pub fn auto_progress(mut self, interval: Duration) -> Self {
+        self.auto_progress = Some(interval);
+        self
+    }
+
+    /// Creates an ordered consumer, which delivers messages in stream order without
+    /// acknowledgements. The consumer is ephemeral and kept in memory with a single replica.
+    ///
+    /// The iterators returned by `PullSubscription::messages` and
+    /// `PullSubscription::messages_with_options` keep it in sequence: whenever a gap in the
+    /// consumer sequence is detected, heartbeats are missed or the consumer is deleted, it is
+    /// recreated right after the last stream sequence that was delivered.
+    pub fn ordered(mut self) -> Self {
+        self.ordered = true;
+        self
+    }
 }
 
 /// Options for subscription
@@ -818,6 +1132,9 @@ pub struct SubscribeOptions {
     pub(crate) flow_control: Option<bool>,
     pub(crate) rate_limit: Option<u64>,
     pub(crate) headers_only: Option<bool>,
+
+    // For message processing:
+    pub(crate) auto_progress: Option<Duration>,
 }
 
 impl SubscribeOptions {
@@ -990,6 +1307,14 @@ impl SubscribeOptions {
         self.idle_heartbeat = Some(interval);
         self
     }
+
+    /// Sends in-progress acknowledgements at the given interval while a message is processed by
+    /// `with_process_handler`, `process` or `process_timeout`, so long running handlers do not
+    /// exceed the consumer's `ack_wait`. Intervals shorter than 100 milliseconds are raised to it.
+    pub fn auto_progress(mut self, interval: Duration) -> Self {
+        self.auto_progress = Some(interval);
+        self
+    }
 }
 
 /// Options for publishing
@@ -1025,6 +1350,163 @@ pub struct AccountInfo {
     pub api: ApiStats,
     /// Limits placed on the accuont
     pub limits: AccountLimits,
+    /// How much memory is reserved by streams with a `max_bytes` limit
+    #[serde(default)]
+    pub reserved_memory: u64,
+    /// How much storage is reserved by streams with a `max_bytes` limit
+    #[serde(default)]
+    pub reserved_storage: u64,
+    /// The `JetStream` domain of the account, if any
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// Usage and limits per replica tier (`R1`, `R3`, ...) for accounts with tiered limits.
+    ///
+    /// When this is not empty, the flat usage and limits above are not populated.
+    #[serde(default)]
+    pub tiers: HashMap<String, Tier>,
+}
+
+impl AccountInfo {
+    /// Returns the usage and limits applying to streams with the given number of replicas.
+    ///
+    /// For accounts without tiered limits this is the account wide usage and limits.
+    pub fn tier(&self, replicas: usize) -> Option<Tier> {
+        if self.tiers.is_empty() {
+            return Some(Tier {
+                memory: self.memory,
+                storage: self.storage,
+                reserved_memory: self.reserved_memory,
+                reserved_storage: self.reserved_storage,
+                streams: self.streams,
+                consumers: self.consumers,
+                limits: self.limits,
+            });
+        }
+
+        self.tiers.get(&format!("R{}", replicas.max(1))).cloned()
+    }
+
+    /// Checks whether a stream with the given configuration fits the remaining quota of the
+    /// account, so it can be rejected before calling `add_stream`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use nats::jetstream::StreamConfig;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// let config = StreamConfig {
+    ///     name: "events".to_string(),
+    ///     max_bytes: 1024 * 1024,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// context.account_info()?.check_stream(&config)?;
+    /// context.add_stream(&config)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn check_stream(&self, config: &StreamConfig) -> io::Result<()> {
+        let replicas = config.num_replicas.max(1);
+        let tier = self.tier(replicas).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::Other,
+                format!("account has no JetStream limits for R{} streams", replicas),
+            )
+        })?;
+        let limits = tier.limits;
+
+        if limits.max_streams >= 0 && tier.streams >= limits.max_streams {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                format!("maximum number of streams reached ({})", limits.max_streams),
+            ));
+        }
+
+        if config.max_consumers > 0
+            && limits.max_consumers > 0
+            && i64::from(config.max_consumers) > limits.max_consumers
+        {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                format!(
+                    "maximum consumers exceed the account limit ({})",
+                    limits.max_consumers
+                ),
+            ));
+        }
+
+        if config.max_bytes <= 0 {
+            if limits.max_bytes_required {
+                return Err(io::Error::new(
+                    ErrorKind::Other,
+                    "account requires streams to set max_bytes",
+                ));
+            }
+            return Ok(());
+        }
+
+        let (max_stream_bytes, max_bytes, reserved) = match config.storage {
+            StorageType::File => (
+                limits.storage_max_stream_bytes,
+                limits.max_storage,
+                tier.reserved_storage,
+            ),
+            StorageType::Memory => (
+                limits.memory_max_stream_bytes,
+                limits.max_memory,
+                tier.reserved_memory,
+            ),
+        };
+
+        if max_stream_bytes > 0 && config.max_bytes > max_stream_bytes {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                format!(
+                    "max_bytes exceeds the maximum bytes per stream ({})",
+                    max_stream_bytes
+                ),
+            ));
+        }
+
+        let required = config
+            .max_bytes
+            .saturating_mul(i64::try_from(replicas).unwrap_or(i64::MAX));
+        let reserved_bytes = i64::try_from(reserved).unwrap_or(i64::MAX);
+        if max_bytes >= 0 && reserved_bytes.saturating_add(required) > max_bytes {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                format!(
+                    "insufficient {:?} resources, {} bytes of {} already reserved",
+                    config.storage, reserved, max_bytes
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Usage and limits of a single replica tier of an account.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Tier {
+    /// How much memory is used
+    pub memory: i64,
+    /// How much storage is used
+    pub storage: i64,
+    /// How much memory is reserved by streams with a `max_bytes` limit
+    #[serde(default)]
+    pub reserved_memory: u64,
+    /// How much storage is reserved by streams with a `max_bytes` limit
+    #[serde(default)]
+    pub reserved_storage: u64,
+    /// How many streams exist
+    pub streams: i64,
+    /// How many consumers exist
+    pub consumers: i64,
+    /// Limits placed on the tier
+    pub limits: AccountLimits,
 }
 
 /// reports on API calls to `JetStream` for this account.
@@ -0,0 +1,333 @@
+// Copyright 2020-2022 The NATS Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+use super::{is_valid_key_pattern, Entry, Operation, Store, WatchEvent, WatchOptions, Watcher};
+
+// How often the watcher thread checks whether the cache was dropped while the bucket is idle.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Options to configure a `CachedStore`.
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    key_filter: String,
+    max_entries: Option<usize>,
+}
+
+impl Default for CacheOptions {
+    fn default() -> CacheOptions {
+        CacheOptions {
+            key_filter: ">".to_string(),
+            max_entries: None,
+        }
+    }
+}
+
+impl CacheOptions {
+    /// Creates a new set of default cache options, which cache every key of the bucket without
+    /// a size bound.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only caches keys matching the pattern, which may contain the `*` and `>` wildcards.
+    /// Other keys are read from the bucket.
+    pub fn key_filter(mut self, pattern: &str) -> Self {
+        self.key_filter = pattern.to_string();
+        self
+    }
+
+    /// Bounds the number of cached entries. When the bound is reached the oldest entries are
+    /// evicted, and reads of evicted keys go to the bucket again.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+}
+
+/// Describes how fresh the values served by a `CachedStore` are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStatus {
+    /// Whether all values present when the cache was created have been loaded.
+    pub ready: bool,
+    /// Whether the cache still receives updates. If not, reads go to the bucket.
+    pub watching: bool,
+    /// The latest revision applied to the cache.
+    pub revision: u64,
+    /// How many updates were still pending delivery when the latest one was applied.
+    pub pending: u64,
+    /// How long ago the latest update was applied, if any.
+    ///
+    /// This keeps growing while nothing is written to the bucket, so on its own it does not mean
+    /// the cache lags behind. A `pending` count of zero means the cache had applied every update
+    /// in the bucket at the time of the latest one.
+    pub since_update: Option<Duration>,
+    /// How many entries are cached.
+    pub entries: usize,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, Entry>,
+    // Keys in insertion order, used to evict the oldest entries first.
+    order: VecDeque<String>,
+    ready: bool,
+    watching: bool,
+    evicted: bool,
+    revision: u64,
+    pending: u64,
+    updated: Option<Instant>,
+}
+
+impl CacheState {
+    fn insert(&mut self, entry: Entry, max_entries: Option<usize>) {
+        if let Some(cached) = self.entries.get_mut(&entry.key) {
+            if cached.revision < entry.revision {
+                *cached = entry;
+            }
+            return;
+        }
+
+        self.order.push_back(entry.key.clone());
+        self.entries.insert(entry.key.clone(), entry);
+
+        if let Some(max_entries) = max_entries {
+            while self.entries.len() > max_entries {
+                match self.order.pop_front() {
+                    Some(key) => {
+                        self.entries.remove(&key);
+                        self.evicted = true;
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+struct Inner {
+    store: Store,
+    options: CacheOptions,
+    state: Mutex<CacheState>,
+    changed: Condvar,
+}
+
+/// A read-through cache of a key value store, kept up to date by a watcher running in a
+/// background thread.
+///
+/// Reads of cached keys are served from memory, without a round trip to the server, and may
+/// lag slightly behind the bucket, see `CachedStore::status`. Writes go to the bucket and show
+/// up in the cache once the watcher delivers them.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use nats::kv::{CacheOptions, CachedStore};
+/// # use std::time::Duration;
+/// # fn main() -> std::io::Result<()> {
+/// # let client = nats::connect("demo.nats.io")?;
+/// # let context = nats::jetstream::new(client);
+/// let store = context.key_value("config")?;
+/// let cache = CachedStore::new(store, CacheOptions::new().max_entries(10_000))?;
+/// cache.wait_ready(Duration::from_secs(5))?;
+///
+/// let value = cache.get("feature.enabled")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CachedStore(Arc<Inner>);
+
+impl fmt::Debug for CachedStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("CachedStore")
+            .field("store", &self.0.store)
+            .field("options", &self.0.options)
+            .finish()
+    }
+}
+
+impl CachedStore {
+    /// Creates a cache of the store and starts watching the keys it caches.
+    pub fn new(store: Store, options: CacheOptions) -> io::Result<CachedStore> {
+        if !is_valid_key_pattern(&options.key_filter) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid key pattern",
+            ));
+        }
+
+        let watcher = store.watch_with_options(&options.key_filter, WatchOptions::new())?;
+
+        let inner = Arc::new(Inner {
+            store,
+            options,
+            state: Mutex::new(CacheState {
+                watching: true,
+                ..Default::default()
+            }),
+            changed: Condvar::new(),
+        });
+
+        let weak = Arc::downgrade(&inner);
+        thread::Builder::new()
+            .name("nats_kv_cache".to_string())
+            .spawn(move || watch(watcher, &weak))?;
+
+        Ok(CachedStore(inner))
+    }
+
+    /// Returns the underlying store.
+    pub fn store(&self) -> &Store {
+        &self.0.store
+    }
+
+    /// Waits until all values present when the cache was created have been loaded.
+    pub fn wait_ready(&self, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.0.state.lock();
+        while !state.ready {
+            if !state.watching {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "cache stopped watching the bucket",
+                ));
+            }
+            if self.0.changed.wait_until(&mut state, deadline).timed_out() {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out waiting for the cache",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the latest entry for the key, if any.
+    ///
+    /// Served from memory once the cache is ready, unless the key is not cached.
+    pub fn entry(&self, key: &str) -> io::Result<Option<Entry>> {
+        if !key_matches(&self.0.options.key_filter, key) {
+            return self.0.store.entry(key);
+        }
+
+        {
+            let state = self.0.state.lock();
+            if state.ready && state.watching {
+                if let Some(entry) = state.entries.get(key) {
+                    return Ok(Some(entry.clone()));
+                }
+                // Without evictions the cache holds every key there is.
+                if !state.evicted {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let entry = self.0.store.entry(key)?;
+        if let Some(ref entry) = entry {
+            let mut state = self.0.state.lock();
+            if state.ready && state.watching {
+                state.insert(entry.clone(), self.0.options.max_entries);
+            }
+        }
+
+        Ok(entry)
+    }
+
+    /// Returns the latest value for the key, if any.
+    ///
+    /// Served from memory once the cache is ready, unless the key is not cached.
+    pub fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entry(key)?.and_then(|entry| {
+            if entry.operation == Operation::Put {
+                Some(entry.value)
+            } else {
+                None
+            }
+        }))
+    }
+
+    /// Returns how fresh the cached values are.
+    pub fn status(&self) -> CacheStatus {
+        let state = self.0.state.lock();
+        CacheStatus {
+            ready: state.ready,
+            watching: state.watching,
+            revision: state.revision,
+            pending: state.pending,
+            since_update: state.updated.map(|updated| updated.elapsed()),
+            entries: state.entries.len(),
+        }
+    }
+}
+
+fn watch(mut watcher: Watcher, inner: &Weak<Inner>) {
+    loop {
+        let result = watcher.next_timeout(POLL_INTERVAL);
+
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        let event = match result {
+            Ok(event) => event,
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => continue,
+            Err(err) => {
+                log::error!("kv cache stopped watching the bucket: {}", err);
+                inner.state.lock().watching = false;
+                inner.changed.notify_all();
+                return;
+            }
+        };
+
+        let mut state = inner.state.lock();
+        match event {
+            WatchEvent::Entry(entry) => {
+                state.revision = state.revision.max(entry.revision);
+                state.pending = entry.delta;
+                state.updated = Some(Instant::now());
+                state.insert(entry, inner.options.max_entries);
+            }
+            WatchEvent::InitialValuesDone => {
+                state.ready = true;
+                inner.changed.notify_all();
+            }
+        }
+    }
+}
+
+// Checks whether a key matches a pattern with `*` and `>` wildcards.
+fn key_matches(pattern: &str, key: &str) -> bool {
+    let mut keys = key.split('.');
+    for token in pattern.split('.') {
+        match (token, keys.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (token, Some(key)) if token == key => {}
+            _ => return false,
+        }
+    }
+
+    keys.next().is_none()
+}
@@ -0,0 +1,590 @@
+// Copyright 2020-2022 The NATS Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel as channel;
+use parking_lot::Mutex;
+
+use super::{Entry, Operation, Store, WatchEvent, WatchOptions, Watcher};
+use crate::jetstream::Error;
+
+// How often background threads check whether they were stopped, and how long they back off
+// after the watcher of the lock failed.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+type Callback = Arc<dyn Fn() + Send + Sync>;
+
+/// A distributed lock stored under a key of a key value store.
+///
+/// The lock is acquired by creating the key, and held by renewing it with revision-checked
+/// updates. The bucket must have a `max_age`, which expires locks abandoned by crashed
+/// holders. Locks are renewed three times per `max_age`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use nats::kv::{Config, Lock};
+/// # use std::time::Duration;
+/// # fn main() -> std::io::Result<()> {
+/// # let client = nats::connect("demo.nats.io")?;
+/// # let context = nats::jetstream::new(client);
+/// let store = context.create_key_value(&Config {
+///     bucket: "locks".to_string(),
+///     max_age: Duration::from_secs(15),
+///     ..Default::default()
+/// })?;
+///
+/// let lock = Lock::new(&store, "migrations")?;
+/// let guard = lock.acquire(Duration::from_secs(60))?;
+/// // Run the migrations while `guard.is_held()`.
+/// guard.release()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Lock {
+    store: Store,
+    key: String,
+    owner: String,
+    ttl: Duration,
+    // A watcher of the key, shared by the clones of the lock and handed to the guard holding it.
+    watcher: Arc<Mutex<Option<Watcher>>>,
+}
+
+impl fmt::Debug for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Lock")
+            .field("store", &self.store)
+            .field("key", &self.key)
+            .field("owner", &self.owner)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl Lock {
+    /// Creates a lock for the key, owned by a unique identifier.
+    pub fn new(store: &Store, key: &str) -> io::Result<Lock> {
+        Lock::with_owner(store, key, &nuid::next())
+    }
+
+    /// Creates a lock for the key, owned by the given identifier.
+    pub fn with_owner(store: &Store, key: &str, owner: &str) -> io::Result<Lock> {
+        let ttl = store.status()?.max_age();
+        if ttl.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "locks require a bucket with a max age",
+            ));
+        }
+
+        Ok(Lock {
+            store: store.clone(),
+            key: key.to_string(),
+            owner: owner.to_string(),
+            ttl,
+            watcher: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Returns the key of the lock.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the identifier of this owner of the lock.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Returns the identifier of the current holder of the lock, if any.
+    pub fn holder(&self) -> io::Result<Option<String>> {
+        Ok(self
+            .store
+            .get(&self.key)?
+            .map(|value| String::from_utf8_lossy(&value).into_owned()))
+    }
+
+    /// Acquires the lock if it is not held by anybody.
+    pub fn try_acquire(&self) -> io::Result<Option<LockGuard>> {
+        let mut watcher = None;
+        let result = self.try_acquire_with(&mut watcher, None);
+        self.return_watcher(watcher);
+        result
+    }
+
+    /// Acquires the lock, waiting until it is released or expires.
+    pub fn acquire(&self, timeout: Duration) -> io::Result<LockGuard> {
+        let deadline = Instant::now() + timeout;
+        let mut watcher = None;
+        let result = loop {
+            match self.try_acquire_with(&mut watcher, None) {
+                Ok(Some(guard)) => break Ok(guard),
+                Ok(None) => {}
+                Err(err) => break Err(err),
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                break Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out acquiring the lock",
+                ));
+            }
+
+            self.wait_for_release(
+                &mut watcher,
+                deadline.min(now + self.renew_interval()),
+                None,
+            );
+        };
+
+        self.return_watcher(watcher);
+        result
+    }
+
+    fn try_acquire_with(
+        &self,
+        watcher: &mut Option<Watcher>,
+        on_lost: Option<Callback>,
+    ) -> io::Result<Option<LockGuard>> {
+        // Watch before acquiring, so no change after acquisition goes unnoticed.
+        self.ensure_watcher(watcher)?;
+
+        match self.store.create(&self.key, &self.owner) {
+            Ok(revision) => Ok(Some(LockGuard::new(
+                self,
+                watcher.take(),
+                revision,
+                on_lost,
+            )?)),
+            Err(err) if Error::from_io_error(&err).map_or(false, Error::is_wrong_last_sequence) => {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // Reuses the watcher of the lock if it is not in use, or creates a new one.
+    fn ensure_watcher(&self, watcher: &mut Option<Watcher>) -> io::Result<()> {
+        if watcher.is_none() {
+            *watcher = self.watcher.lock().take();
+        }
+        if watcher.is_none() {
+            *watcher = Some(
+                self.store
+                    .watch_with_options(&self.key, WatchOptions::new().updates_only())?,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn return_watcher(&self, watcher: Option<Watcher>) {
+        if let Some(watcher) = watcher {
+            let mut shared = self.watcher.lock();
+            if shared.is_none() {
+                *shared = Some(watcher);
+            }
+        }
+    }
+
+    // Waits up to the timeout for the next change of the key. Watcher failures are retried
+    // with a new watcher after backing off, so callers never spin.
+    fn next_change(
+        &self,
+        watcher: &mut Option<Watcher>,
+        timeout: Duration,
+        stop: Option<&channel::Receiver<()>>,
+    ) -> Option<Entry> {
+        let result = match self.ensure_watcher(watcher) {
+            Ok(()) => match watcher
+                .as_mut()
+                .map(|watcher| watcher.next_timeout(timeout))
+            {
+                Some(Ok(WatchEvent::Entry(entry))) => return Some(entry),
+                Some(Err(err)) if err.kind() != io::ErrorKind::TimedOut => Err(err),
+                _ => Ok(()),
+            },
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            log::warn!("failed to watch lock {}: {}", self.key, err);
+            watcher.take();
+            match stop {
+                Some(stop) => {
+                    stop.recv_timeout(POLL_INTERVAL).ok();
+                }
+                None => thread::sleep(POLL_INTERVAL),
+            }
+        }
+
+        None
+    }
+
+    fn renew_interval(&self) -> Duration {
+        self.ttl / 3
+    }
+
+    // Waits until the deadline, or until the lock is released, returning true if stopped.
+    fn wait_for_release(
+        &self,
+        watcher: &mut Option<Watcher>,
+        deadline: Instant,
+        stop: Option<&channel::Receiver<()>>,
+    ) -> bool {
+        loop {
+            if stop.map_or(false, is_stopped) {
+                return true;
+            }
+
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return false;
+            }
+
+            if let Some(entry) = self.next_change(watcher, timeout.min(POLL_INTERVAL), stop) {
+                if entry.operation != Operation::Put {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+fn is_stopped(stop: &channel::Receiver<()>) -> bool {
+    !matches!(stop.try_recv(), Err(channel::TryRecvError::Empty))
+}
+
+struct GuardState {
+    held: AtomicBool,
+    revision: AtomicU64,
+}
+
+/// Holds a `Lock` until it is released or dropped, renewing it from a background thread.
+pub struct LockGuard {
+    store: Store,
+    key: String,
+    state: Arc<GuardState>,
+    stop: Option<channel::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for LockGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("LockGuard")
+            .field("key", &self.key)
+            .field("held", &self.is_held())
+            .finish()
+    }
+}
+
+impl LockGuard {
+    fn new(
+        lock: &Lock,
+        mut watcher: Option<Watcher>,
+        revision: u64,
+        on_lost: Option<Callback>,
+    ) -> io::Result<LockGuard> {
+        let state = Arc::new(GuardState {
+            held: AtomicBool::new(true),
+            revision: AtomicU64::new(revision),
+        });
+        let (stop, stopped) = channel::bounded::<()>(1);
+
+        let thread = {
+            let lock = lock.clone();
+            let state = state.clone();
+            thread::Builder::new()
+                .name("nats_kv_lock".to_string())
+                .spawn(move || {
+                    let mut renewed = Instant::now();
+                    let lost = loop {
+                        if is_stopped(&stopped) {
+                            break false;
+                        }
+
+                        let next_renewal = renewed + lock.renew_interval();
+                        let timeout = next_renewal
+                            .saturating_duration_since(Instant::now())
+                            .min(POLL_INTERVAL);
+
+                        // Any later change not made by us means somebody else took over the
+                        // lock. A reused watcher may still deliver changes from before.
+                        let lost = match lock.next_change(&mut watcher, timeout, Some(&stopped)) {
+                            Some(entry) => {
+                                entry.revision > revision
+                                    && (entry.operation != Operation::Put
+                                        || entry.value != lock.owner.as_bytes())
+                            }
+                            None => false,
+                        };
+
+                        let lost = lost || {
+                            Instant::now() >= next_renewal
+                                && match lock.store.update(
+                                    &lock.key,
+                                    &lock.owner,
+                                    state.revision.load(Ordering::Acquire),
+                                ) {
+                                    Ok(revision) => {
+                                        state.revision.store(revision, Ordering::Release);
+                                        renewed = Instant::now();
+                                        false
+                                    }
+                                    Err(err) => {
+                                        log::warn!("failed to renew lock {}: {}", lock.key, err);
+                                        Error::from_io_error(&err)
+                                            .map_or(false, Error::is_wrong_last_sequence)
+                                            || renewed.elapsed() >= lock.ttl
+                                    }
+                                }
+                        };
+
+                        if lost {
+                            break true;
+                        }
+                    };
+
+                    lock.return_watcher(watcher);
+                    if lost {
+                        state.held.store(false, Ordering::Release);
+                        if let Some(on_lost) = on_lost {
+                            on_lost();
+                        }
+                    }
+                })?
+        };
+
+        Ok(LockGuard {
+            store: lock.store.clone(),
+            key: lock.key.clone(),
+            state,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns true while the lock is held.
+    pub fn is_held(&self) -> bool {
+        self.state.held.load(Ordering::Acquire)
+    }
+
+    /// Returns the revision of the key the lock is held at.
+    pub fn revision(&self) -> u64 {
+        self.state.revision.load(Ordering::Acquire)
+    }
+
+    /// Releases the lock. Same as dropping the guard, but reports failures to release it.
+    pub fn release(mut self) -> io::Result<()> {
+        self.do_release()
+    }
+
+    fn do_release(&mut self) -> io::Result<()> {
+        // Wait for the renewing thread, so the revision does not change underneath us.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+
+        if self.state.held.swap(false, Ordering::AcqRel) {
+            self.store.delete_if_revision(&self.key, self.revision())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        self.do_release().ok();
+    }
+}
+
+/// Elects a single leader among the candidates campaigning for the same `Lock`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use nats::kv::{LeaderElection, Lock};
+/// # fn main() -> std::io::Result<()> {
+/// # let client = nats::connect("demo.nats.io")?;
+/// # let context = nats::jetstream::new(client);
+/// # let store = context.key_value("locks")?;
+/// let campaign = LeaderElection::new(Lock::new(&store, "scheduler")?)
+///     .on_elected(|| println!("elected"))
+///     .on_lost(|| println!("no longer the leader"))
+///     .campaign()?;
+///
+/// // Do leader work while `campaign.is_leader()`.
+/// campaign.resign();
+/// # Ok(())
+/// # }
+/// ```
+pub struct LeaderElection {
+    lock: Lock,
+    on_elected: Option<Callback>,
+    on_lost: Option<Callback>,
+}
+
+impl fmt::Debug for LeaderElection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("LeaderElection")
+            .field("lock", &self.lock)
+            .finish()
+    }
+}
+
+impl LeaderElection {
+    /// Creates a leader election for the lock.
+    pub fn new(lock: Lock) -> LeaderElection {
+        LeaderElection {
+            lock,
+            on_elected: None,
+            on_lost: None,
+        }
+    }
+
+    /// Sets a callback invoked when this candidate becomes the leader.
+    pub fn on_elected<F>(mut self, callback: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_elected = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets a callback invoked when this candidate stops being the leader, including when it
+    /// resigns.
+    pub fn on_lost<F>(mut self, callback: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_lost = Some(Arc::new(callback));
+        self
+    }
+
+    /// Starts campaigning for leadership from a background thread, until the returned
+    /// `Campaign` is resigned or dropped. Leadership lost is campaigned for again.
+    pub fn campaign(self) -> io::Result<Campaign> {
+        let leader = Arc::new(AtomicBool::new(false));
+        let (stop, stopped) = channel::bounded::<()>(1);
+        let mut watcher = None;
+        self.lock.ensure_watcher(&mut watcher)?;
+
+        let thread = {
+            let leader = leader.clone();
+            let on_lost: Callback = {
+                let leader = leader.clone();
+                let on_lost = self.on_lost.clone();
+                Arc::new(move || {
+                    if leader.swap(false, Ordering::AcqRel) {
+                        if let Some(ref on_lost) = on_lost {
+                            on_lost();
+                        }
+                    }
+                })
+            };
+
+            thread::Builder::new()
+                .name("nats_kv_leader_election".to_string())
+                .spawn(move || {
+                    let lock = self.lock;
+                    loop {
+                        let guard = lock
+                            .try_acquire_with(&mut watcher, Some(on_lost.clone()))
+                            .unwrap_or_else(|err| {
+                                log::warn!("failed to campaign for {}: {}", lock.key, err);
+                                None
+                            });
+
+                        let guard = if let Some(guard) = guard {
+                            guard
+                        } else {
+                            let deadline = Instant::now() + lock.renew_interval();
+                            if lock.wait_for_release(&mut watcher, deadline, Some(&stopped)) {
+                                return;
+                            }
+                            continue;
+                        };
+
+                        leader.store(true, Ordering::Release);
+                        if let Some(ref on_elected) = self.on_elected {
+                            on_elected();
+                        }
+
+                        loop {
+                            if let Err(channel::RecvTimeoutError::Timeout) =
+                                stopped.recv_timeout(POLL_INTERVAL)
+                            {
+                                if !guard.is_held() {
+                                    break;
+                                }
+                            } else {
+                                drop(guard);
+                                on_lost();
+                                return;
+                            }
+                        }
+                    }
+                })?
+        };
+
+        Ok(Campaign {
+            leader,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+/// An ongoing campaign for leadership, which resigns when dropped.
+pub struct Campaign {
+    leader: Arc<AtomicBool>,
+    stop: Option<channel::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for Campaign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Campaign")
+            .field("leader", &self.is_leader())
+            .finish()
+    }
+}
+
+impl Campaign {
+    /// Returns true while this candidate is the leader.
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::Acquire)
+    }
+
+    /// Stops campaigning, releasing the leadership if held. Same as dropping the campaign.
+    pub fn resign(self) {}
+}
+
+impl Drop for Campaign {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
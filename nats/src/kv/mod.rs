@@ -0,0 +1,1876 @@
+// Copyright 2020-2022 The NATS Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for Key Value Store.
+//! This feature is experimental and the API may change.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::header::{self, HeaderMap};
+use crate::jetstream::{
+    ClusterInfo, DateTime, DiscardPolicy, Error, JetStream, PagedIterator, PurgeRequest,
+    PushSubscription, Source, StorageType, StreamConfig, StreamInfo, StreamMessage,
+    SubjectTransform, SubscribeOptions,
+};
+use crate::message::Message;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+mod cached;
+mod lock;
+mod typed;
+
+pub use cached::*;
+pub use lock::*;
+pub use typed::*;
+
+/// Configuration values for key value stores.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Name of the bucket
+    pub bucket: String,
+    /// Human readable description.
+    pub description: String,
+    /// Maximum size of a single value.
+    pub max_value_size: i32,
+    /// Maximum historical entries.
+    pub history: i64,
+    /// Maximum age of any entry in the bucket, expressed in nanoseconds
+    pub max_age: Duration,
+    /// How large the bucket may become in total bytes before the configured discard policy kicks in
+    pub max_bytes: i64,
+    /// The type of storage backend, `File` (default) and `Memory`
+    pub storage: StorageType,
+    /// How many replicas to keep for each entry in a cluster.
+    pub num_replicas: usize,
+    /// Makes the bucket a read-only replica of another bucket, possibly in another `JetStream`
+    /// domain. Writes to a mirror are sent to the bucket it mirrors, while reads stay local.
+    /// Cannot be combined with `sources`, and cannot be changed once the bucket exists.
+    pub mirror: Option<Source>,
+    /// Buckets, possibly in other `JetStream` domains, whose entries are copied into this bucket.
+    pub sources: Vec<Source>,
+}
+
+const MAX_HISTORY: i64 = 64;
+const ALL_KEYS: &str = ">";
+
+const KV_OPERATION: &str = "KV-Operation";
+const KV_OPERATION_DELETE: &str = "DEL";
+const KV_OPERATION_PURGE: &str = "PURGE";
+
+// Bounds the optimistic retries of `Store::update_with`.
+const UPDATE_ATTEMPTS: u32 = 10;
+const UPDATE_MAX_BACKOFF: Duration = Duration::from_millis(250);
+
+// How long `Store::watch_from_revision` waits for the first pending revision to check for gaps.
+const REVISION_GAP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const NATS_ROLLUP: &str = "Nats-Rollup";
+const ROLLUP_SUBJECT: &str = "sub";
+
+/// Describes what kind of operation and entry represents
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Operation {
+    /// A value was put into the bucket
+    Put,
+    /// A value was deleted from a bucket
+    Delete,
+    /// A value was purged from a bucket
+    Purge,
+}
+
+// Helper to extract key value operation from message headers
+fn kv_operation_from_maybe_headers(maybe_headers: Option<&HeaderMap>) -> Operation {
+    if let Some(headers) = maybe_headers {
+        if let Some(op) = headers.get(KV_OPERATION) {
+            return match op.as_str() {
+                KV_OPERATION_DELETE => Operation::Delete,
+                KV_OPERATION_PURGE => Operation::Purge,
+                _ => Operation::Put,
+            };
+        }
+    }
+
+    Operation::Put
+}
+
+fn kv_operation_from_stream_message(message: &StreamMessage) -> Operation {
+    kv_operation_from_maybe_headers(message.headers.as_ref())
+}
+
+lazy_static! {
+    static ref VALID_BUCKET_RE: Regex = Regex::new(r#"\A[a-zA-Z0-9_-]+\z"#).unwrap();
+    static ref VALID_KEY_RE: Regex = Regex::new(r#"\A[-/_=\.a-zA-Z0-9]+\z"#).unwrap();
+}
+
+fn is_valid_bucket_name(bucket_name: &str) -> bool {
+    VALID_BUCKET_RE.is_match(bucket_name)
+}
+
+fn is_valid_key(key: &str) -> bool {
+    if key.is_empty() || key.starts_with('.') || key.ends_with('.') {
+        return false;
+    }
+
+    VALID_KEY_RE.is_match(key)
+}
+
+// Returns the name of the bucket a source refers to, along with the source pointing to its stream.
+fn bucket_source(source: &Source) -> (String, Source) {
+    let mut source = source.clone();
+    let bucket = if let Some(bucket) = source.name.strip_prefix("KV_") {
+        bucket.to_string()
+    } else {
+        let bucket = source.name.clone();
+        source.name = format!("KV_{}", bucket);
+        bucket
+    };
+
+    (bucket, source)
+}
+
+// Returns the sources of the stream backing a bucket, renaming the subjects of other buckets.
+fn bucket_sources(config: &Config) -> Vec<Source> {
+    config
+        .sources
+        .iter()
+        .map(|source| {
+            let (origin, mut source) = bucket_source(source);
+            if source.external.is_none() || origin != config.bucket {
+                source.subject_transforms = vec![SubjectTransform {
+                    source: format!("$KV.{}.>", origin),
+                    destination: format!("$KV.{}.>", config.bucket),
+                }];
+            }
+            source
+        })
+        .collect()
+}
+
+fn is_valid_key_pattern(pattern: &str) -> bool {
+    let mut tokens = pattern.split('.').peekable();
+    while let Some(token) = tokens.next() {
+        let valid = match token {
+            "*" => true,
+            ">" => tokens.peek().is_none(),
+            _ => is_valid_key(token),
+        };
+        if !valid {
+            return false;
+        }
+    }
+
+    true
+}
+
+// Default to 1 for history. Max is 64 for now.
+fn history_from_config(config: &Config) -> io::Result<i64> {
+    if config.history > MAX_HISTORY {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "history limited to a max of 64",
+        ));
+    }
+
+    Ok(config.history.max(1))
+}
+
+impl JetStream {
+    /// Bind to an existing key-value store bucket.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// context.create_key_value(&Config {
+    ///   bucket: "key_value".to_string(),
+    ///   ..Default::default()
+    /// })?;
+    ///
+    /// let key_value = context.key_value("key_value")?;
+    ///
+    /// # context.delete_key_value("key_value")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn key_value(&self, bucket: &str) -> io::Result<Store> {
+        if !self.connection.is_server_compatible_version(2, 6, 2) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "key-value requires at least server version 2.6.2",
+            ));
+        }
+
+        if !is_valid_bucket_name(bucket) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid bucket name",
+            ));
+        }
+
+        let stream_name = format!("KV_{}", bucket);
+        let stream_info = self.stream_info(&stream_name)?;
+
+        // Do some quick sanity checks that this is a correctly formed stream for KV.
+        // Max msgs per subject should be > 0.
+        if stream_info.config.max_msgs_per_subject < 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "bucket not valid key-value store",
+            ));
+        }
+
+        Ok(Store::new(self, bucket, &stream_info))
+    }
+
+    /// Create a new key-value store bucket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// let bucket = context.create_key_value(&Config {
+    ///   bucket: "create_key_value".to_string(),
+    ///   ..Default::default()
+    /// })?;
+    ///
+    /// # context.delete_key_value("create_key_value")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_key_value(&self, config: &Config) -> io::Result<Store> {
+        if !self.connection.is_server_compatible_version(2, 6, 2) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "key-value requires at least server version 2.6.2",
+            ));
+        }
+
+        let discard_policy = {
+            if self.connection.is_server_compatible_version(2, 7, 2) {
+                DiscardPolicy::New
+            } else {
+                DiscardPolicy::Old
+            }
+        };
+
+        if !is_valid_bucket_name(&config.bucket) {
+            return Err(io::Error::new(io::ErrorKind::Other, "invalid bucket name"));
+        }
+
+        self.account_info()?;
+
+        let history = history_from_config(config)?;
+
+        let num_replicas = if config.num_replicas == 0 {
+            1
+        } else {
+            config.num_replicas
+        };
+
+        let allow_direct = self.connection.is_server_compatible_version(2, 9, 0);
+
+        if config.mirror.is_some() && !config.sources.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a bucket cannot have both a mirror and sources",
+            ));
+        }
+
+        let mut subjects = vec![format!("$KV.{}.>", config.bucket)];
+        let mut mirror = None;
+        if let Some(ref source) = config.mirror {
+            // Mirrors keep the subjects of the origin bucket and cannot have their own.
+            let (_, source) = bucket_source(source);
+            mirror = Some(source);
+            subjects.clear();
+        }
+        let sources = bucket_sources(config);
+
+        let stream_info = self.add_stream(&StreamConfig {
+            name: format!("KV_{}", config.bucket),
+            description: Some(config.description.to_string()),
+            subjects,
+            max_msgs_per_subject: history,
+            max_bytes: config.max_bytes,
+            max_age: config.max_age,
+            max_msg_size: config.max_value_size,
+            storage: config.storage,
+            allow_rollup: true,
+            deny_delete: true,
+            num_replicas,
+            discard: discard_policy,
+            allow_direct,
+            mirror_direct: allow_direct && mirror.is_some(),
+            mirror,
+            sources,
+            ..Default::default()
+        })?;
+
+        Ok(Store::new(self, &config.bucket, &stream_info))
+    }
+
+    /// Updates the configuration of an existing key value store bucket, such as its history,
+    /// maximum age and size limits.
+    ///
+    /// The storage type and mirror of a bucket cannot be changed, and a `num_replicas` of 0
+    /// keeps the current number of replicas. The sources of the bucket are replaced by
+    /// `config.sources`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// context.create_key_value(&Config {
+    ///   bucket: "update_key_value".to_string(),
+    ///   ..Default::default()
+    /// })?;
+    ///
+    /// context.update_key_value(&Config {
+    ///   bucket: "update_key_value".to_string(),
+    ///   history: 5,
+    ///   ..Default::default()
+    /// })?;
+    ///
+    /// # context.delete_key_value("update_key_value")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_key_value(&self, config: &Config) -> io::Result<Store> {
+        if !is_valid_bucket_name(&config.bucket) {
+            return Err(io::Error::new(io::ErrorKind::Other, "invalid bucket name"));
+        }
+
+        let history = history_from_config(config)?;
+
+        let stream_name = format!("KV_{}", config.bucket);
+        let mut stream_config = self.stream_info(&stream_name)?.config;
+        if let Some(ref source) = config.mirror {
+            let (_, source) = bucket_source(source);
+            if stream_config.mirror.as_ref().map(|mirror| &mirror.name) != Some(&source.name) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the mirror of a bucket cannot be changed",
+                ));
+            }
+        }
+        if stream_config.mirror.is_some() && !config.sources.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a bucket cannot have both a mirror and sources",
+            ));
+        }
+        stream_config.description = Some(config.description.to_string());
+        stream_config.max_msgs_per_subject = history;
+        stream_config.max_bytes = config.max_bytes;
+        stream_config.max_age = config.max_age;
+        stream_config.max_msg_size = config.max_value_size;
+        if config.num_replicas > 0 {
+            stream_config.num_replicas = config.num_replicas;
+        }
+        stream_config.sources = bucket_sources(config);
+
+        let stream_info = self.update_stream(&stream_config)?;
+
+        Ok(Store::new(self, &config.bucket, &stream_info))
+    }
+
+    /// Returns an iterator over the status of all key value store buckets.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// for status in context.key_value_buckets() {
+    ///     let status = status?;
+    ///     println!("{}: {} bytes", status.bucket(), status.bytes());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn key_value_buckets(&self) -> KeyValueBuckets<'_> {
+        KeyValueBuckets {
+            streams: self.list_streams(),
+        }
+    }
+
+    /// Delete the specified key value store bucket.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "delete_key_value".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    ///
+    /// context.delete_key_value("delete_key_value")?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn delete_key_value(&self, bucket: &str) -> io::Result<()> {
+        if !self.connection.is_server_compatible_version(2, 6, 2) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "key-value requires at least server version 2.6.2",
+            ));
+        }
+
+        if !is_valid_bucket_name(bucket) {
+            return Err(io::Error::new(io::ErrorKind::Other, "invalid bucket name"));
+        }
+
+        let stream_name = format!("KV_{}", bucket);
+        self.delete_stream(&stream_name)?;
+
+        Ok(())
+    }
+}
+
+/// An entry in a key-value bucket.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Name of the bucket the entry is in.
+    pub bucket: String,
+    /// The key that was retrieved.
+    pub key: String,
+    /// The value that was retreived.
+    pub value: Vec<u8>,
+    /// A unique sequence for this value.
+    pub revision: u64,
+    /// Distance from the latest value.
+    pub delta: u64,
+    /// The time the data was put in the bucket.
+    pub created: DateTime,
+    /// The kind of operation that caused this entry.
+    pub operation: Operation,
+}
+
+/// A key value store
+#[derive(Debug, Clone)]
+pub struct Store {
+    name: String,
+    stream_name: String,
+    prefix: String,
+    put_prefix: String,
+    allow_direct: bool,
+    context: JetStream,
+}
+
+impl Store {
+    fn new(context: &JetStream, bucket: &str, info: &StreamInfo) -> Store {
+        // Entries of a mirror keep the subjects of the origin bucket, which is also where writes
+        // go, through the API prefix of its domain if it lives in another one.
+        let (prefix, put_prefix) = if let Some(ref mirror) = info.config.mirror {
+            let origin = mirror.name.strip_prefix("KV_").unwrap_or(&mirror.name);
+            let prefix = format!("$KV.{}.", origin);
+            let put_prefix = match mirror.external {
+                Some(ref external) if !external.api_prefix.is_empty() => {
+                    format!("{}.$KV.{}.", external.api_prefix, origin)
+                }
+                _ => prefix.clone(),
+            };
+            (prefix, put_prefix)
+        } else {
+            let prefix = format!("$KV.{}.", bucket);
+            (prefix.clone(), prefix)
+        };
+
+        Store {
+            name: bucket.to_string(),
+            stream_name: info.config.name.clone(),
+            prefix,
+            put_prefix,
+            allow_direct: info.config.allow_direct,
+            context: context.clone(),
+        }
+    }
+
+    /// Returns the status of the bucket
+    pub fn status(&self) -> io::Result<BucketStatus> {
+        let info = self.context.stream_info(&self.stream_name)?;
+
+        Ok(BucketStatus {
+            bucket: self.name.to_string(),
+            info,
+        })
+    }
+
+    /// Returns the latest entry for the key, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "entry_bucket".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.put("foo", b"bar")?;
+    /// let maybe_entry = bucket.entry("foo")?;
+    /// if let Some(entry) = maybe_entry {
+    ///   println!("Found entry {:?}", entry);
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn entry(&self, key: &str) -> io::Result<Option<Entry>> {
+        if !is_valid_key(key) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid key"));
+        }
+
+        let mut subject = String::new();
+        subject.push_str(&self.prefix);
+        subject.push_str(key);
+
+        let result = if self.allow_direct {
+            self.context
+                .direct_get_last_for_subject(&self.stream_name, &subject)
+        } else {
+            self.context.get_last_message(&self.stream_name, &subject)
+        };
+
+        match result {
+            Ok(message) => {
+                let operation = kv_operation_from_stream_message(&message);
+                let entry = Entry {
+                    bucket: self.name.clone(),
+                    key: key.to_string(),
+                    value: message.data,
+                    revision: message.sequence,
+                    created: message.time,
+                    operation,
+                    delta: 0,
+                };
+
+                Ok(Some(entry))
+            }
+            Err(err) => {
+                if Error::from_io_error(&err).map_or(false, Error::is_no_message_found) {
+                    return Ok(None);
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the latest value for the key, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "get".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.put("foo", b"bar")?;
+    /// let maybe_value = bucket.get("foo")?;
+    /// if let Some(value) = maybe_value {
+    ///   println!("Found value {:?}", value);
+    /// }
+    /// #
+    /// # context.delete_key_value("get")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.entry(key) {
+            Ok(Some(entry)) => match entry.operation {
+                Operation::Put => Ok(Some(entry.value)),
+                _ => Ok(None),
+            },
+            Ok(None) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Places the new value for the key into the bucket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "put".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.put("foo", b"bar")?;
+    /// # context.delete_key_value("put")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn put(&self, key: &str, value: impl AsRef<[u8]>) -> io::Result<u64> {
+        if !is_valid_key(key) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid key"));
+        }
+
+        let mut subject = String::new();
+        subject.push_str(&self.put_prefix);
+        subject.push_str(key);
+
+        let publish_ack = self.context.publish(&subject, value)?;
+
+        Ok(publish_ack.sequence)
+    }
+
+    /// Creates the key/value pair if it does not exist or is marked for deletion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "create".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.purge("foo")?;
+    /// bucket.create("foo", b"bar")?;
+    /// #
+    /// # context.delete_key_value("create")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create(&self, key: &str, value: impl AsRef<[u8]>) -> io::Result<u64> {
+        let result = self.update(key, &value, 0);
+        if result.is_ok() {
+            return result;
+        }
+
+        // Check if the last entry is a delete marker
+        if let Ok(Some(entry)) = self.entry(key) {
+            if entry.operation != Operation::Put {
+                return self.update(key, &value, entry.revision);
+            }
+        }
+
+        result
+    }
+
+    /// Updates the value if the latest revision matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "update".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// let revision = bucket.put("foo", b"bar")?;
+    /// let new_revision = bucket.update("foo", b"baz", revision)?;
+    /// # context.delete_key_value("update")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update(&self, key: &str, value: impl AsRef<[u8]>, revision: u64) -> io::Result<u64> {
+        if !is_valid_key(key) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid key"));
+        }
+
+        let mut subject = String::new();
+        subject.push_str(&self.put_prefix);
+        subject.push_str(key);
+
+        let mut headers = HeaderMap::default();
+        headers.insert(
+            header::NATS_EXPECTED_LAST_SUBJECT_SEQUENCE,
+            revision.to_string(),
+        );
+
+        let message = Message::new(&subject, None, value, Some(headers));
+        let publish_ack = self.context.publish_message(&message)?;
+
+        Ok(publish_ack.sequence)
+    }
+
+    /// Updates the value of the key with the result of `f`, which is called with the latest
+    /// value, or `None` if the key does not exist or is deleted.
+    ///
+    /// The update is optimistic: if the key changes concurrently, `f` is called again with the
+    /// new value, after a short randomized backoff, up to a bounded number of attempts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "update_with".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.update_with("foo", |value| {
+    ///     let mut value = value.unwrap_or_default().to_vec();
+    ///     value.extend_from_slice(b"bar");
+    ///     value
+    /// })?;
+    /// # context.delete_key_value("update_with")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_with<F>(&self, key: &str, mut f: F) -> io::Result<u64>
+    where
+        F: FnMut(Option<&[u8]>) -> Vec<u8>,
+    {
+        self.try_update_with(key, |value| Ok(f(value)))
+    }
+
+    /// Adds `delta` to the integer counter stored under the key, returning the new value.
+    /// Missing or deleted keys count as zero. Counters are stored as decimal strings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "increment".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.increment("visits", 1)?;
+    /// assert_eq!(bucket.increment("visits", 2)?, 3);
+    /// # context.delete_key_value("increment")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn increment(&self, key: &str, delta: i64) -> io::Result<i64> {
+        let mut counter = 0;
+        self.try_update_with(key, |value| {
+            let current = match value {
+                Some(value) => std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|value| value.trim().parse::<i64>().ok())
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "value is not an integer")
+                    })?,
+                None => 0,
+            };
+
+            counter = current
+                .checked_add(delta)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "counter overflow"))?;
+
+            Ok(counter.to_string().into_bytes())
+        })?;
+
+        Ok(counter)
+    }
+
+    fn try_update_with<F>(&self, key: &str, mut f: F) -> io::Result<u64>
+    where
+        F: FnMut(Option<&[u8]>) -> io::Result<Vec<u8>>,
+    {
+        let mut backoff = Duration::from_millis(5);
+        let mut attempt = 1;
+        loop {
+            let entry = self.entry(key)?;
+            let result = match entry {
+                Some(entry) if entry.operation == Operation::Put => {
+                    let value = f(Some(&entry.value))?;
+                    self.update(key, value, entry.revision)
+                }
+                Some(entry) => self.update(key, f(None)?, entry.revision),
+                None => self.update(key, f(None)?, 0),
+            };
+
+            match result {
+                Err(err)
+                    if attempt < UPDATE_ATTEMPTS
+                        && Error::from_io_error(&err)
+                            .map_or(false, Error::is_wrong_last_sequence) =>
+                {
+                    let jitter =
+                        fastrand::u64(0..=u64::try_from(backoff.as_millis()).unwrap_or(u64::MAX));
+                    std::thread::sleep(Duration::from_millis(jitter));
+                    backoff = (backoff * 2).min(UPDATE_MAX_BACKOFF);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Marks an entry as deleted by placing a delete marker but leaves the revision history intact.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "delete".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.create("foo", b"bar")?;
+    /// bucket.delete("foo")?;
+    /// #
+    /// # context.delete_key_value("delete");
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete(&self, key: &str) -> io::Result<()> {
+        self.publish_marker(key, Operation::Delete, None)
+    }
+
+    /// Marks an entry as deleted, like `delete`, but only if the latest revision of the key is
+    /// `revision`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "delete_if_revision".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// let revision = bucket.create("foo", b"bar")?;
+    /// bucket.delete_if_revision("foo", revision)?;
+    /// #
+    /// # context.delete_key_value("delete_if_revision")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete_if_revision(&self, key: &str, revision: u64) -> io::Result<()> {
+        self.publish_marker(key, Operation::Delete, Some(revision))
+    }
+
+    /// Remove any entries associated with the key and all historical revisions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "purge".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.create("foo", b"bar")?;
+    /// bucket.purge("foo")?;
+    /// # context.delete_key_value("purge")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn purge(&self, key: &str) -> io::Result<()> {
+        self.publish_marker(key, Operation::Purge, None)
+    }
+
+    /// Removes any entries associated with the key, like `purge`, but only if the latest
+    /// revision of the key is `revision`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "purge_if_revision".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// let revision = bucket.create("foo", b"bar")?;
+    /// bucket.purge_if_revision("foo", revision)?;
+    /// # context.delete_key_value("purge_if_revision")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn purge_if_revision(&self, key: &str, revision: u64) -> io::Result<()> {
+        self.publish_marker(key, Operation::Purge, Some(revision))
+    }
+
+    fn publish_marker(
+        &self,
+        key: &str,
+        operation: Operation,
+        revision: Option<u64>,
+    ) -> io::Result<()> {
+        if !is_valid_key(key) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid key"));
+        }
+
+        let mut subject = String::new();
+        subject.push_str(&self.put_prefix);
+        subject.push_str(key);
+
+        let mut headers = HeaderMap::default();
+        match operation {
+            Operation::Purge => {
+                headers.insert(KV_OPERATION, KV_OPERATION_PURGE.to_string());
+                headers.insert(NATS_ROLLUP, ROLLUP_SUBJECT.to_string());
+            }
+            Operation::Delete => {
+                headers.insert(KV_OPERATION, KV_OPERATION_DELETE.to_string());
+            }
+            Operation::Put => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "only delete and purge markers can be published",
+                ));
+            }
+        }
+        if let Some(revision) = revision {
+            headers.insert(
+                header::NATS_EXPECTED_LAST_SUBJECT_SEQUENCE,
+                revision.to_string(),
+            );
+        }
+
+        let message = Message::new(&subject, None, b"", Some(headers));
+        self.context.publish_message(&message)?;
+
+        Ok(())
+    }
+
+    /// Removes the delete and purge markers of the bucket together with any revisions preceding
+    /// them.
+    ///
+    /// Markers younger than `older_than` are kept, so watchers which are lagging behind still
+    /// observe the removal of the key. A zero duration removes all markers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # use std::time::Duration;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "purge_deletes".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.put("foo", b"bar")?;
+    /// bucket.delete("foo")?;
+    /// bucket.purge_deletes(Duration::from_secs(30 * 60))?;
+    /// # context.delete_key_value("purge_deletes")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn purge_deletes(&self, older_than: Duration) -> io::Result<()> {
+        let subscription = self.context.subscribe_with_options(
+            &format!("{}{}", self.prefix, ALL_KEYS),
+            &self
+                .ordered_options()
+                .headers_only()
+                .deliver_last_per_subject(),
+        )?;
+
+        // Without any keys there is no message to tell us we are done.
+        let info = subscription.consumer_info()?;
+        let mut done = info.num_pending == 0 && info.delivered.consumer_seq == 0;
+
+        let mut markers = Vec::new();
+        while !done {
+            let message = match subscription.next() {
+                Some(message) => message,
+                None => break,
+            };
+            let info = match message.jetstream_message_info() {
+                Some(info) => info,
+                None => continue,
+            };
+            if kv_operation_from_maybe_headers(message.headers.as_ref()) != Operation::Put {
+                markers.push((message.subject.clone(), info.published));
+            }
+            done = info.pending == 0;
+        }
+        subscription.unsubscribe()?;
+
+        let now = DateTime::now_utc();
+        for (subject, created) in markers {
+            let keep = if now - created < older_than {
+                Some(1)
+            } else {
+                None
+            };
+
+            self.context.purge_stream_with_options(
+                &self.stream_name,
+                &PurgeRequest {
+                    filter: Some(subject),
+                    keep,
+                    ..Default::default()
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns an iterator which iterate over all the current keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "keys".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.put("foo", b"fizz")?;
+    /// bucket.put("bar", b"buzz")?;
+    ///
+    /// let mut keys = bucket.keys()?;
+    ///
+    /// assert_eq!(keys.next(), Some("foo".to_string()));
+    /// assert_eq!(keys.next(), Some("bar".to_string()));
+    /// #
+    /// # context.delete_key_value("keys")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keys(&self) -> io::Result<Keys> {
+        self.keys_with_filter(ALL_KEYS)
+    }
+
+    /// Returns an iterator over the current keys matching a pattern, which may contain the `*`
+    /// and `>` wildcards.
+    ///
+    /// Only the latest revision of each matching key is delivered, without values, and keys
+    /// which are deleted or purged are skipped, see `keys_with_options` to include them. Keys are
+    /// fetched lazily while iterating.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "keys_with_filter".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.put("users.1.profile", b"fizz")?;
+    /// bucket.put("users.1.settings", b"buzz")?;
+    ///
+    /// let mut keys = bucket.keys_with_filter("users.*.profile")?;
+    ///
+    /// assert_eq!(keys.next(), Some("users.1.profile".to_string()));
+    /// assert_eq!(keys.next(), None);
+    /// #
+    /// # context.delete_key_value("keys_with_filter")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keys_with_filter(&self, pattern: &str) -> io::Result<Keys> {
+        self.keys_with_options(&KeysOptions::new().filter(pattern))
+    }
+
+    /// Returns an iterator over the current keys of the bucket, as configured by `options`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::{Config, KeysOptions};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// # let bucket = context.create_key_value(&Config {
+    /// #  bucket: "keys_with_options".to_string(),
+    /// #  ..Default::default()
+    /// # })?;
+    /// #
+    /// bucket.put("foo", b"fizz")?;
+    /// bucket.delete("foo")?;
+    ///
+    /// let mut keys = bucket.keys_with_options(&KeysOptions::new().include_deleted())?;
+    ///
+    /// assert_eq!(keys.next(), Some("foo".to_string()));
+    /// assert_eq!(keys.next(), None);
+    /// #
+    /// # context.delete_key_value("keys_with_options")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keys_with_options(&self, options: &KeysOptions) -> io::Result<Keys> {
+        let pattern = options.filter.as_str();
+        if !is_valid_key_pattern(pattern) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid key pattern",
+            ));
+        }
+
+        let mut subject = String::new();
+        subject.push_str(&self.prefix);
+        subject.push_str(pattern);
+
+        let subscription = self.context.subscribe_with_options(
+            &subject,
+            &self
+                .ordered_options()
+                .headers_only()
+                .deliver_last_per_subject(),
+        )?;
+
+        // Without any matching keys there is no message to tell us we are done.
+        let info = subscription.consumer_info()?;
+        let done = info.num_pending == 0 && info.delivered.consumer_seq == 0;
+
+        Ok(Keys {
+            prefix: self.prefix.clone(),
+            subscription,
+            include_deleted: options.include_deleted,
+            done,
+        })
+    }
+
+    /// Returns an iterator which iterates over each entry in historical order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use nats::kv::Config;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// #
+    /// let bucket = context.create_key_value(&Config {
+    ///   bucket: "history_iter".to_string(),
+    ///   history: 2,
+    ///   ..Default::default()
+    /// })?;
+    ///
+    /// bucket.put("foo", b"fizz")?;
+    /// bucket.put("foo", b"buzz")?;
+    ///
+    /// let mut history = bucket.history("foo")?;
+    ///
+    /// let next = history.next().unwrap();
+    /// assert_eq!(next.key, "foo".to_string());
+    /// assert_eq!(next.value, b"fizz");
+    ///
+    /// let next = history.next().unwrap();
+    /// assert_eq!(next.key, "foo".to_string());
+    /// assert_eq!(next.value, b"buzz");
+    ///
+    /// # context.delete_key_value("history_iter")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn history(&self, key: &str) -> io::Result<History> {
+        let mut subject = String::new();
+        subject.push_str(&self.prefix);
+        subject.push_str(key);
+
+        let subscription = self.context.subscribe_with_options(
+            &subject,
+            &self
+                .ordered_options()
+                .deliver_all()
+                .enable_flow_control()
+                .idle_heartbeat(Duration::from_millis(5000)),
+        )?;
+
+        Ok(History {
+            bucket: self.name.clone(),
+            prefix: self.prefix.clone(),
+            subscription,
+            done: false,
+        })
+    }
+
+    /// Returns an iterator which iterates over each entry as they happen.
+    pub fn watch_all(&self) -> io::Result<Watch> {
+        self.watch(">")
+    }
+
+    /// Returns an iterator which iterates over each entry for specific key pattern as they happen.
+    pub fn watch<T: AsRef<str>>(&self, key: T) -> io::Result<Watch> {
+        Ok(Watch {
+            watcher: self.watch_with_options(key, WatchOptions::new())?,
+        })
+    }
+
+    /// Returns an iterator over the entries for a key pattern, configured by `options`.
+    ///
+    /// Unlike `watch`, the iterator yields `WatchEvent::InitialValuesDone` once all values
+    /// present at the time of subscribing have been delivered.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nats::kv::{WatchEvent, WatchOptions};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// # let bucket = context.key_value("config")?;
+    /// let mut watcher = bucket.watch_with_options(">", WatchOptions::new().ignore_deletes())?;
+    ///
+    /// for event in &mut watcher {
+    ///     match event {
+    ///         WatchEvent::Entry(entry) => println!("{} = {:?}", entry.key, entry.value),
+    ///         WatchEvent::InitialValuesDone => break,
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_with_options<T: AsRef<str>>(
+        &self,
+        key: T,
+        options: WatchOptions,
+    ) -> io::Result<Watcher> {
+        if options.updates_only && options.include_history {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "updates only cannot be combined with include history",
+            ));
+        }
+
+        let mut subscribe_options = self
+            .ordered_options()
+            .enable_flow_control()
+            .idle_heartbeat(Duration::from_millis(5000));
+        subscribe_options = if options.updates_only {
+            subscribe_options.deliver_new()
+        } else if options.include_history {
+            subscribe_options.deliver_all()
+        } else {
+            subscribe_options.deliver_last_per_subject()
+        };
+        if options.meta_only {
+            subscribe_options = subscribe_options.headers_only();
+        }
+
+        self.watcher(key.as_ref(), &subscribe_options, options)
+    }
+
+    /// Returns an iterator over the entries for a key pattern written after `revision`, such as
+    /// the last revision applied before a restart.
+    ///
+    /// The iterator yields `WatchEvent::InitialValuesDone` once it caught up with the entries
+    /// present at the time of subscribing.
+    ///
+    /// Fails with a `RevisionGap` error if revisions following `revision` are no longer retained
+    /// by the bucket, for example because they exceeded its `max_age` or the history kept per
+    /// key, in which case the caller has to resynchronize all values instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nats::kv::{RevisionGap, WatchEvent};
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// # let bucket = context.key_value("config")?;
+    /// # let last_applied = 42;
+    /// match bucket.watch_from_revision(">", last_applied) {
+    ///     Ok(watcher) => {
+    ///         for event in watcher {
+    ///             if let WatchEvent::Entry(entry) = event {
+    ///                 println!("{} changed at revision {}", entry.key, entry.revision);
+    ///             }
+    ///         }
+    ///     }
+    ///     Err(err) if RevisionGap::from_io_error(&err).is_some() => {
+    ///         // Reload everything with `watch_with_options` instead.
+    ///     }
+    ///     Err(err) => return Err(err),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_from_revision<T: AsRef<str>>(&self, key: T, revision: u64) -> io::Result<Watcher> {
+        let subscribe_options = self
+            .ordered_options()
+            .deliver_by_start_sequence(revision + 1)
+            .enable_flow_control()
+            .idle_heartbeat(Duration::from_millis(5000));
+
+        let mut watcher = self.watcher(key.as_ref(), &subscribe_options, WatchOptions::new())?;
+
+        // The consumer starts at the first retained revision, so everything between the
+        // requested revision and that one is lost.
+        let info = self.context.stream_info(&self.stream_name)?;
+        if info.state.first_seq > revision + 1 && info.state.last_seq > revision {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                RevisionGap {
+                    revision,
+                    first_revision: info.state.first_seq,
+                },
+            ));
+        }
+
+        // Revisions of a key are also discarded by the history limit, which other keys can hide
+        // from the check above. That requires the watched keys to hold at least a full history
+        // of newer revisions, in which case the first of them tells whether any went missing.
+        let pending = watcher.subscription.consumer_info()?.num_pending;
+        let history = u64::try_from(info.config.max_msgs_per_subject.max(1)).unwrap_or(1);
+        if pending > 0 && pending >= history {
+            let message = watcher.subscription.next_timeout(REVISION_GAP_TIMEOUT)?;
+            let first_revision = message
+                .jetstream_message_info()
+                .map_or(revision + 1, |info| info.stream_seq);
+            watcher.peeked = Some(message);
+
+            if first_revision > revision + 1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    RevisionGap {
+                        revision,
+                        first_revision,
+                    },
+                ));
+            }
+        }
+
+        Ok(watcher)
+    }
+
+    fn watcher(
+        &self,
+        key: &str,
+        subscribe_options: &SubscribeOptions,
+        options: WatchOptions,
+    ) -> io::Result<Watcher> {
+        let subject = format!("{}{}", self.prefix, key);
+        let subscription = self
+            .context
+            .subscribe_with_options(subject.as_str(), subscribe_options)?;
+
+        // Nothing to replay if the consumer had no pending messages and delivered none yet.
+        let initial_done = options.updates_only || {
+            let info = subscription.consumer_info()?;
+            info.num_pending == 0 && info.delivered.consumer_seq == 0
+        };
+
+        Ok(Watcher {
+            bucket: self.name.clone(),
+            prefix: self.prefix.clone(),
+            subscription,
+            ignore_deletes: options.ignore_deletes,
+            initial_done,
+            marker_pending: initial_done,
+            peeked: None,
+        })
+    }
+
+    // Ordered consumers are bound to the stream of the bucket, since mirrors have no subjects
+    // to look it up by.
+    fn ordered_options(&self) -> SubscribeOptions {
+        SubscribeOptions {
+            stream_name: Some(self.stream_name.clone()),
+            ..SubscribeOptions::ordered()
+        }
+    }
+
+    /// Returns the name of the bucket
+    pub fn bucket(&self) -> &String {
+        &self.name
+    }
+}
+
+/// Options to configure `Store::keys_with_options`.
+#[derive(Debug, Clone)]
+pub struct KeysOptions {
+    filter: String,
+    include_deleted: bool,
+}
+
+impl Default for KeysOptions {
+    fn default() -> KeysOptions {
+        KeysOptions {
+            filter: ALL_KEYS.to_string(),
+            include_deleted: false,
+        }
+    }
+}
+
+impl KeysOptions {
+    /// Creates a new set of default options, which list every key that is not deleted or
+    /// purged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only lists keys matching the pattern, which may contain the `*` and `>` wildcards.
+    pub fn filter(mut self, pattern: &str) -> Self {
+        self.filter = pattern.to_string();
+        self
+    }
+
+    /// Also lists keys whose latest entry is a delete or purge marker.
+    pub fn include_deleted(mut self) -> Self {
+        self.include_deleted = true;
+        self
+    }
+}
+
+/// An iterator used to iterate through the keys of a bucket.
+pub struct Keys {
+    prefix: String,
+    subscription: PushSubscription,
+    include_deleted: bool,
+    done: bool,
+}
+
+impl Iterator for Keys {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let message = self.subscription.next()?;
+
+            // If there are no more pending messages we'll stop after delivering the key
+            // derived from this message.
+            if let Some(info) = message.jetstream_message_info() {
+                if info.pending == 0 {
+                    self.done = true;
+                }
+            }
+
+            // We are only interested in unique current keys from subjects so we skip delete
+            // and purge markers, unless asked not to.
+            let operation = kv_operation_from_maybe_headers(message.headers.as_ref());
+            if operation != Operation::Put && !self.include_deleted {
+                continue;
+            }
+
+            return message
+                .subject
+                .strip_prefix(&self.prefix)
+                .map(|s| s.to_string());
+        }
+
+        None
+    }
+}
+
+/// An iterator used to iterate through the history of a bucket.
+pub struct History {
+    bucket: String,
+    prefix: String,
+    subscription: PushSubscription,
+    done: bool,
+}
+
+impl Iterator for History {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.subscription.next() {
+            Some(message) => {
+                if let Some(info) = message.jetstream_message_info() {
+                    if info.pending == 0 {
+                        self.done = true;
+                    }
+
+                    let operation = kv_operation_from_maybe_headers(message.headers.as_ref());
+
+                    let key = message
+                        .subject
+                        .strip_prefix(&self.prefix)
+                        .map(|s| s.to_string())
+                        .unwrap();
+
+                    Some(Entry {
+                        bucket: self.bucket.clone(),
+                        key,
+                        value: message.data.clone(),
+                        revision: info.stream_seq,
+                        created: info.published,
+                        delta: info.pending,
+                        operation,
+                    })
+                } else {
+                    None
+                }
+            }
+
+            None => None,
+        }
+    }
+}
+
+/// An iterator used to watch changes in a bucket, yielding only the entries of a `Watcher`.
+pub struct Watch {
+    watcher: Watcher,
+}
+
+impl Iterator for Watch {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let WatchEvent::Entry(entry) = self.watcher.next()? {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+/// Options to configure `Store::watch_with_options`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WatchOptions {
+    updates_only: bool,
+    include_history: bool,
+    ignore_deletes: bool,
+    meta_only: bool,
+}
+
+impl WatchOptions {
+    /// Creates a new set of default watch options, which replay the latest value of each key
+    /// before delivering updates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only delivers changes made after the watcher was created.
+    pub fn updates_only(mut self) -> Self {
+        self.updates_only = true;
+        self
+    }
+
+    /// Replays all historical values of each key instead of only the latest.
+    pub fn include_history(mut self) -> Self {
+        self.include_history = true;
+        self
+    }
+
+    /// Skips delete and purge markers.
+    pub fn ignore_deletes(mut self) -> Self {
+        self.ignore_deletes = true;
+        self
+    }
+
+    /// Only delivers the metadata of entries, leaving their values empty.
+    pub fn meta_only(mut self) -> Self {
+        self.meta_only = true;
+        self
+    }
+}
+
+/// Error returned by `Store::watch_from_revision` when revisions following the requested one are
+/// no longer retained by the bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RevisionGap {
+    /// The revision the watcher was requested to resume from.
+    pub revision: u64,
+    /// The oldest revision of the watched keys still retained by the bucket.
+    pub first_revision: u64,
+}
+
+impl RevisionGap {
+    /// Returns the revision gap wrapped by an `io::Error`, if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&RevisionGap> {
+        err.get_ref()
+            .and_then(|inner| inner.downcast_ref::<RevisionGap>())
+    }
+}
+
+impl fmt::Display for RevisionGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "revisions after {} are no longer retained, the oldest revision is {}",
+            self.revision, self.first_revision
+        )
+    }
+}
+
+impl error::Error for RevisionGap {}
+
+/// An event yielded by a `Watcher`.
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    /// An entry was delivered.
+    Entry(Entry),
+    /// All values present when the watcher was created have been delivered.
+    InitialValuesDone,
+}
+
+/// An iterator used to watch changes in a bucket, created by `Store::watch_with_options`.
+pub struct Watcher {
+    bucket: String,
+    prefix: String,
+    subscription: PushSubscription,
+    ignore_deletes: bool,
+    initial_done: bool,
+    marker_pending: bool,
+    // A message received ahead of time, delivered before any other.
+    peeked: Option<Message>,
+}
+
+impl Watcher {
+    /// Waits for the next event until the timeout elapses.
+    pub fn next_timeout(&mut self, timeout: Duration) -> io::Result<WatchEvent> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.take_marker() {
+                return Ok(event);
+            }
+
+            let message = if let Some(message) = self.peeked.take() {
+                message
+            } else {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                self.subscription.next_timeout(timeout)?
+            };
+            if let Some(entry) = self.entry(&message) {
+                return Ok(WatchEvent::Entry(entry));
+            }
+        }
+    }
+
+    /// Stops the watcher and deletes its consumer.
+    pub fn unsubscribe(self) -> io::Result<()> {
+        self.subscription.unsubscribe()
+    }
+
+    fn take_marker(&mut self) -> Option<WatchEvent> {
+        if self.marker_pending {
+            self.marker_pending = false;
+            Some(WatchEvent::InitialValuesDone)
+        } else {
+            None
+        }
+    }
+
+    fn entry(&mut self, message: &Message) -> Option<Entry> {
+        let info = message.jetstream_message_info()?;
+        if !self.initial_done && info.pending == 0 {
+            self.initial_done = true;
+            self.marker_pending = true;
+        }
+
+        let operation = kv_operation_from_maybe_headers(message.headers.as_ref());
+        if self.ignore_deletes && operation != Operation::Put {
+            return None;
+        }
+
+        let key = message.subject.strip_prefix(&self.prefix)?.to_string();
+
+        Some(Entry {
+            bucket: self.bucket.clone(),
+            key,
+            value: message.data.clone(),
+            revision: info.stream_seq,
+            created: info.published,
+            delta: info.pending,
+            operation,
+        })
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = WatchEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.take_marker() {
+                return Some(event);
+            }
+
+            let message = if let Some(message) = self.peeked.take() {
+                message
+            } else {
+                self.subscription.next()?
+            };
+            if let Some(entry) = self.entry(&message) {
+                return Some(WatchEvent::Entry(entry));
+            }
+        }
+    }
+}
+
+/// Represents status information about a key value store bucket
+#[derive(Debug, Clone)]
+pub struct BucketStatus {
+    info: StreamInfo,
+    bucket: String,
+}
+
+impl BucketStatus {
+    /// The name of the bucket
+    pub fn bucket(&self) -> &String {
+        &self.bucket
+    }
+
+    /// How many messages are in the bucket, including historical values
+    pub fn values(&self) -> u64 {
+        self.info.state.messages
+    }
+
+    /// Configured history kept per key
+    pub fn history(&self) -> i64 {
+        self.info.config.max_msgs_per_subject
+    }
+
+    /// How long the bucket keeps values for
+    pub fn max_age(&self) -> Duration {
+        self.info.config.max_age
+    }
+
+    /// How many bytes the bucket uses, including historical values
+    pub fn bytes(&self) -> u64 {
+        self.info.state.bytes
+    }
+
+    /// How large the bucket may become in total bytes, -1 if unlimited
+    pub fn max_bytes(&self) -> i64 {
+        self.info.config.max_bytes
+    }
+
+    /// The largest value accepted by the bucket, -1 if unlimited
+    pub fn max_value_size(&self) -> i32 {
+        self.info.config.max_msg_size
+    }
+
+    /// The type of storage backend of the bucket
+    pub fn storage(&self) -> StorageType {
+        self.info.config.storage
+    }
+
+    /// How many replicas are kept for each entry
+    pub fn replicas(&self) -> usize {
+        self.info.config.num_replicas
+    }
+
+    /// Information about the cluster hosting the bucket
+    pub fn cluster(&self) -> &ClusterInfo {
+        &self.info.cluster
+    }
+
+    /// The information about the stream backing the bucket
+    pub fn stream_info(&self) -> &StreamInfo {
+        &self.info
+    }
+}
+
+/// An iterator over the key value store buckets, created by `JetStream::key_value_buckets`.
+pub struct KeyValueBuckets<'a> {
+    streams: PagedIterator<'a, StreamInfo>,
+}
+
+impl Iterator for KeyValueBuckets<'_> {
+    type Item = io::Result<BucketStatus>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let info = match self.streams.next()? {
+                Ok(info) => info,
+                Err(err) => return Some(Err(err)),
+            };
+
+            if let Some(bucket) = info.config.name.strip_prefix("KV_") {
+                return Some(Ok(BucketStatus {
+                    bucket: bucket.to_string(),
+                    info,
+                }));
+            }
+        }
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright 2020-2022 The NATS Authors
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{Entry, History, Operation, Store, Watch};
+use crate::jetstream::DateTime;
+
+/// The error type returned by codecs.
+pub type CodecError = Box<dyn error::Error + Send + Sync>;
+
+/// Converts values stored in a `TypedStore` from and to bytes.
+pub trait Codec<T> {
+    /// Encodes a value into bytes.
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+
+    /// Decodes a value from bytes.
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError>;
+}
+
+/// Encodes values as JSON.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Json;
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for Json {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// Encodes values as CBOR.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for Cbor {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(value, &mut data)?;
+        Ok(data)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError> {
+        Ok(ciborium::de::from_reader(data)?)
+    }
+}
+
+/// Encodes values as `MessagePack`, with structs encoded as maps.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for MessagePack {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        Ok(rmp_serde::to_vec_named(value)?)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError> {
+        Ok(rmp_serde::from_slice(data)?)
+    }
+}
+
+/// Stores values as they are, for byte and string values.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Raw;
+
+impl Codec<Vec<u8>> for Raw {
+    fn encode(&self, value: &Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(value.clone())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        Ok(data.to_vec())
+    }
+}
+
+impl Codec<String> for Raw {
+    fn encode(&self, value: &String) -> Result<Vec<u8>, CodecError> {
+        Ok(value.as_bytes().to_vec())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<String, CodecError> {
+        Ok(String::from_utf8(data.to_vec())?)
+    }
+}
+
+/// Error returned when a value of a `TypedStore` cannot be decoded.
+#[derive(Debug)]
+pub struct DecodeError {
+    /// The key of the entry which failed to decode.
+    pub key: String,
+    /// The revision of the entry which failed to decode.
+    pub revision: u64,
+    source: CodecError,
+}
+
+impl DecodeError {
+    /// Returns the decode error wrapped by an `io::Error`, if any.
+    pub fn from_io_error(err: &io::Error) -> Option<&DecodeError> {
+        err.get_ref()
+            .and_then(|inner| inner.downcast_ref::<DecodeError>())
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to decode key {} at revision {}: {}",
+            self.key, self.revision, self.source
+        )
+    }
+}
+
+impl error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// An entry in a key-value bucket with a decoded value.
+#[derive(Debug, Clone)]
+pub struct TypedEntry<T> {
+    /// Name of the bucket the entry is in.
+    pub bucket: String,
+    /// The key that was retrieved.
+    pub key: String,
+    /// The decoded value, `None` for delete and purge markers.
+    pub value: Option<T>,
+    /// A unique sequence for this value.
+    pub revision: u64,
+    /// Distance from the latest value.
+    pub delta: u64,
+    /// The time the data was put in the bucket.
+    pub created: DateTime,
+    /// The kind of operation that caused this entry.
+    pub operation: Operation,
+}
+
+/// A key value store holding values of type `T`, encoded by the codec `C`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use nats::kv::{Config, TypedStore};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize)]
+/// struct Profile {
+///     name: String,
+/// }
+///
+/// # fn main() -> std::io::Result<()> {
+/// # let client = nats::connect("demo.nats.io")?;
+/// # let context = nats::jetstream::new(client);
+/// let store = context.create_key_value(&Config {
+///     bucket: "profiles".to_string(),
+///     ..Default::default()
+/// })?;
+///
+/// let profiles = TypedStore::<Profile>::new(store);
+/// profiles.put("alice", &Profile { name: "Alice".to_string() })?;
+///
+/// let profile = profiles.get("alice")?.unwrap();
+/// assert_eq!(profile.name, "Alice");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TypedStore<T, C = Json> {
+    store: Store,
+    codec: C,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, C: Clone> Clone for TypedStore<T, C> {
+    fn clone(&self) -> Self {
+        TypedStore {
+            store: self.store.clone(),
+            codec: self.codec.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> TypedStore<T, Json>
+where
+    Json: Codec<T>,
+{
+    /// Wraps a store, encoding values as JSON.
+    pub fn new(store: Store) -> Self {
+        TypedStore::with_codec(store, Json)
+    }
+}
+
+impl<T, C> TypedStore<T, C>
+where
+    C: Codec<T> + Clone,
+{
+    /// Wraps a store, encoding values with the given codec.
+    pub fn with_codec(store: Store, codec: C) -> Self {
+        TypedStore {
+            store,
+            codec,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the underlying store.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Returns the latest entry for the key, if any.
+    pub fn entry(&self, key: &str) -> io::Result<Option<TypedEntry<T>>> {
+        self.store
+            .entry(key)?
+            .map(|entry| decode_entry(&self.codec, entry))
+            .transpose()
+    }
+
+    /// Returns the latest value for the key, if any.
+    pub fn get(&self, key: &str) -> io::Result<Option<T>> {
+        Ok(self.entry(key)?.and_then(|entry| entry.value))
+    }
+
+    /// Places a new value for the key into the store.
+    pub fn put(&self, key: &str, value: &T) -> io::Result<u64> {
+        self.store.put(key, self.encode(value)?)
+    }
+
+    /// Places the value for the key into the store if and only if the key does not exist.
+    pub fn create(&self, key: &str, value: &T) -> io::Result<u64> {
+        self.store.create(key, self.encode(value)?)
+    }
+
+    /// Updates the value for the key if the latest revision matches.
+    pub fn update(&self, key: &str, value: &T, revision: u64) -> io::Result<u64> {
+        self.store.update(key, self.encode(value)?, revision)
+    }
+
+    /// Marks an entry as deleted by placing a delete marker.
+    pub fn delete(&self, key: &str) -> io::Result<()> {
+        self.store.delete(key)
+    }
+
+    /// Returns an iterator which iterates over each entry for a key pattern as they happen.
+    pub fn watch(&self, key: &str) -> io::Result<TypedWatch<T, C>> {
+        Ok(TypedWatch {
+            watch: self.store.watch(key)?,
+            codec: self.codec.clone(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns an iterator which iterates over each entry of a key in historical order.
+    pub fn history(&self, key: &str) -> io::Result<TypedHistory<T, C>> {
+        Ok(TypedHistory {
+            history: self.store.history(key)?,
+            codec: self.codec.clone(),
+            _marker: PhantomData,
+        })
+    }
+
+    fn encode(&self, value: &T) -> io::Result<Vec<u8>> {
+        self.codec
+            .encode(value)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+}
+
+fn decode_entry<T, C: Codec<T>>(codec: &C, entry: Entry) -> io::Result<TypedEntry<T>> {
+    let value = match entry.operation {
+        Operation::Put => Some(codec.decode(&entry.value).map_err(|source| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                DecodeError {
+                    key: entry.key.clone(),
+                    revision: entry.revision,
+                    source,
+                },
+            )
+        })?),
+        Operation::Delete | Operation::Purge => None,
+    };
+
+    Ok(TypedEntry {
+        bucket: entry.bucket,
+        key: entry.key,
+        value,
+        revision: entry.revision,
+        delta: entry.delta,
+        created: entry.created,
+        operation: entry.operation,
+    })
+}
+
+/// An iterator used to watch changes in a `TypedStore`.
+pub struct TypedWatch<T, C> {
+    watch: Watch,
+    codec: C,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, C: Codec<T>> Iterator for TypedWatch<T, C> {
+    type Item = io::Result<TypedEntry<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.watch
+            .next()
+            .map(|entry| decode_entry(&self.codec, entry))
+    }
+}
+
+/// An iterator used to iterate through the history of a key in a `TypedStore`.
+pub struct TypedHistory<T, C> {
+    history: History,
+    codec: C,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, C: Codec<T>> Iterator for TypedHistory<T, C> {
+    type Item = io::Result<TypedEntry<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.history
+            .next()
+            .map(|entry| decode_entry(&self.codec, entry))
+    }
+}
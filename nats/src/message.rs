@@ -17,6 +17,7 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use crate::{
@@ -28,6 +29,8 @@ use time::OffsetDateTime;
 
 pub(crate) const MESSAGE_NOT_BOUND: &str = "message not bound to a connection";
 
+const ACK_SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// A message received on a subject.
 #[derive(Clone)]
 pub struct Message {
@@ -52,6 +55,11 @@ pub struct Message {
     /// using `JetStream`.
     #[doc(hidden)]
     pub double_acked: Arc<AtomicBool>,
+
+    /// Whether a final acknowledgement (ack, nak or term) has been sent for
+    /// this message using `JetStream`.
+    #[doc(hidden)]
+    pub acknowledged: Arc<AtomicBool>,
 }
 
 impl From<crate::asynk::Message> for Message {
@@ -63,6 +71,7 @@ impl From<crate::asynk::Message> for Message {
             headers: asynk.headers,
             client: asynk.client,
             double_acked: asynk.double_acked,
+            acknowledged: asynk.acknowledged,
         }
     }
 }
@@ -190,7 +199,9 @@ impl Message {
         if self.double_acked.load(Ordering::Acquire) {
             return Ok(());
         }
-        self.respond(b"")
+        self.respond(b"")?;
+        self.acknowledged.store(true, Ordering::Release);
+        Ok(())
     }
 
     /// Acknowledge a `JetStream` message. See `AckKind` documentation for
@@ -199,7 +210,98 @@ impl Message {
     ///
     /// Does not check whether this message has already been double-acked.
     pub fn ack_kind(&self, ack_kind: crate::jetstream::AckKind) -> io::Result<()> {
-        self.respond(ack_kind)
+        self.respond(ack_kind)?;
+        if !matches!(ack_kind, crate::jetstream::AckKind::Progress) {
+            self.acknowledged.store(true, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    /// Periodically tells the server that work on this message is still in progress, so it is
+    /// not redelivered while a long running handler processes it. An in-progress
+    /// acknowledgement is sent every `interval` until the returned guard is dropped, or the
+    /// message is acked, naked or termed.
+    ///
+    /// The interval should be well below the consumer's `ack_wait`. Intervals shorter than 100
+    /// milliseconds are raised to it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # fn main() -> std::io::Result<()> {
+    /// # let client = nats::connect("demo.nats.io")?;
+    /// # let context = nats::jetstream::new(client);
+    /// # let subscription = context.subscribe("work")?;
+    /// let message = subscription.next().unwrap();
+    ///
+    /// let progress = message.auto_progress(Duration::from_secs(10));
+    /// // ... long running work ...
+    /// drop(progress);
+    ///
+    /// message.ack()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn auto_progress(&self, interval: Duration) -> crate::jetstream::ProgressGuard {
+        crate::jetstream::ProgressGuard::new(self.clone(), interval)
+    }
+
+    /// Signals that the message will not be processed now and asks the server to redeliver it
+    /// after the given delay, instead of the one configured on the consumer.
+    ///
+    /// Does not check whether this message has already been double-acked.
+    pub fn nak_with_delay(&self, delay: Duration) -> io::Result<()> {
+        self.respond(format!("-NAK {{\"delay\":{}}}", delay.as_nanos()))?;
+        self.acknowledged.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Acknowledge a `JetStream` message and wait for the server to confirm it received the ack.
+    /// Unlike `double_ack` the ack is sent only once, and a `TimedOut` error is returned if the
+    /// server does not confirm it in time.
+    ///
+    /// Returns immediately if this message has already been double-acked.
+    pub fn ack_sync(&self) -> io::Result<()> {
+        if self.double_acked.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let original_reply = self.reply.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "No reply subject available (not a JetStream message)",
+            )
+        })?;
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, MESSAGE_NOT_BOUND))?;
+
+        let ack_reply = format!("_INBOX.{}", nuid::next());
+        let (sid, receiver) = client.subscribe(&ack_reply, None)?;
+        let sub = crate::Subscription::new(sid, ack_reply.clone(), receiver, client.clone());
+
+        client.publish(
+            original_reply,
+            Some(&ack_reply),
+            None,
+            crate::jetstream::AckKind::Ack.as_ref(),
+        )?;
+
+        sub.next_timeout(ACK_SYNC_TIMEOUT).map_err(|err| {
+            if err.kind() == io::ErrorKind::TimedOut {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "ack_sync: ack was not confirmed by the server",
+                )
+            } else {
+                err
+            }
+        })?;
+
+        self.double_acked.store(true, Ordering::Release);
+        self.acknowledged.store(true, Ordering::Release);
+        Ok(())
     }
 
     /// Acknowledge a `JetStream` message and wait for acknowledgement from the server
@@ -234,7 +336,7 @@ impl Message {
             let ack_reply = format!("_INBOX.{}", nuid::next());
             let sub_ret = client.subscribe(&ack_reply, None);
             if sub_ret.is_err() {
-                std::thread::sleep(std::time::Duration::from_millis(100));
+                std::thread::sleep(Duration::from_millis(100));
                 continue;
             }
             let (sid, receiver) = sub_ret?;
@@ -243,14 +345,12 @@ impl Message {
 
             let pub_ret = client.publish(original_reply, Some(&ack_reply), None, ack_kind.as_ref());
             if pub_ret.is_err() {
-                std::thread::sleep(std::time::Duration::from_millis(100));
+                std::thread::sleep(Duration::from_millis(100));
                 continue;
             }
-            if sub
-                .next_timeout(std::time::Duration::from_millis(100))
-                .is_ok()
-            {
+            if sub.next_timeout(Duration::from_millis(100)).is_ok() {
                 self.double_acked.store(true, Ordering::Release);
+                self.acknowledged.store(true, Ordering::Release);
                 return Ok(());
             }
         }
@@ -395,6 +495,7 @@ impl Default for Message {
             headers: None,
             client: None,
             double_acked: Arc::new(AtomicBool::new(false)),
+            acknowledged: Arc::new(AtomicBool::new(false)),
         }
     }
 }
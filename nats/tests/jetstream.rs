@@ -784,3 +784,881 @@ fn jetstream_pull_subscribe_bad_stream() {
     js.pull_subscribe("WRONG")
         .expect_err("expected not found stream for a given subject");
 }
+
+#[test]
+fn jetstream_direct_get() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string(), "bar".to_string()],
+        allow_direct: true,
+        ..Default::default()
+    })
+    .unwrap();
+
+    js.publish("foo", b"1").unwrap();
+    js.publish("bar", b"2").unwrap();
+    js.publish("foo", b"3").unwrap();
+
+    let message = js.direct_get("TEST", 2).unwrap();
+    assert_eq!(message.subject, "bar");
+    assert_eq!(message.sequence, 2);
+    assert_eq!(message.data, b"2");
+
+    let message = js.direct_get_last_for_subject("TEST", "foo").unwrap();
+    assert_eq!(message.sequence, 3);
+    assert_eq!(message.data, b"3");
+
+    let message = js.direct_get_next_for_subject("TEST", "foo", 2).unwrap();
+    assert_eq!(message.sequence, 3);
+
+    let err = js.direct_get("TEST", 100).unwrap_err();
+    let err = err.get_ref().unwrap().downcast_ref::<Error>().unwrap();
+    assert_eq!(err.error_code(), ErrorCode::NoMessageFound);
+}
+
+#[test]
+fn jetstream_publish_async() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = JetStream::new(nc, JetStreamOptions::new().publish_async_max_pending(10));
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let acks = (0..100)
+        .map(|i| js.publish_async("foo", format!("message {}", i)).unwrap())
+        .collect::<Vec<_>>();
+
+    js.publish_async_complete_timeout(Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(js.publish_async_pending(), 0);
+
+    for (i, ack) in acks.into_iter().enumerate() {
+        let ack = ack.wait_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(ack.stream, "TEST");
+        assert_eq!(ack.sequence, i as u64 + 1);
+    }
+
+    let ack = js
+        .publish_async_with_options(
+            "foo",
+            b"bar",
+            &PublishOptions {
+                expected_stream: Some("WRONG".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    ack.wait().expect_err("expected stream mismatch");
+}
+
+#[test]
+fn jetstream_api_errors() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    let err = js.stream_info("MISSING").unwrap_err();
+    let err = Error::from_io_error(&err).unwrap();
+    assert!(err.is_stream_not_found());
+    assert_eq!(err.code(), 404);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let err = js.consumer_info("TEST", "MISSING").unwrap_err();
+    assert!(Error::from_io_error(&err).unwrap().is_consumer_not_found());
+
+    let err = js
+        .pull_subscribe_with_options(
+            "foo",
+            &PullSubscribeOptions::new().durable_name("MISSING".to_string()),
+        )
+        .unwrap_err();
+    assert!(Error::from_io_error(&err).unwrap().is_consumer_not_found());
+
+    js.publish("foo", b"1").unwrap();
+    let err = js
+        .publish_with_options(
+            "foo",
+            b"2",
+            &PublishOptions {
+                expected_last_sequence: Some(5),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+    assert!(Error::from_io_error(&err).unwrap().is_wrong_last_sequence());
+}
+
+#[test]
+fn jetstream_snapshot_restore() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    for i in 0..100 {
+        js.publish("foo", format!("{}", i)).unwrap();
+    }
+
+    let mut snapshot = Vec::new();
+    let info = js.snapshot_stream("TEST", &mut snapshot).unwrap();
+    assert_eq!(info.state.messages, 100);
+    assert!(!snapshot.is_empty());
+
+    js.delete_stream("TEST").unwrap();
+
+    let info = js.restore_stream(&info.config, &snapshot[..]).unwrap();
+    assert_eq!(info.state.messages, 100);
+
+    let message = js.get_message("TEST", 50).unwrap();
+    assert_eq!(message.data, b"49");
+}
+
+#[test]
+fn jetstream_pull_subscribe_fetch_status() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    for _ in 0..5 {
+        js.publish("foo", b"lorem").unwrap();
+    }
+
+    let consumer = js.pull_subscribe("foo").unwrap();
+
+    let mut batch = consumer.fetch(5).unwrap();
+    for message in &mut batch {
+        message.ack().unwrap();
+    }
+    assert_eq!(batch.status(), None);
+
+    let mut batch = consumer
+        .fetch(BatchOptions {
+            batch: 10,
+            no_wait: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    assert!(batch.next().is_none());
+    assert_eq!(batch.status(), Some(&PullStatus::NoMessages));
+
+    let mut batch = consumer
+        .timeout_fetch(
+            BatchOptions {
+                batch: 10,
+                expires: Some(Duration::from_millis(200).as_nanos() as usize),
+                idle_heartbeat: Duration::from_millis(50),
+                ..Default::default()
+            },
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+    assert!(batch.next().is_none());
+    assert_eq!(batch.status(), Some(&PullStatus::RequestTimeout));
+}
+
+#[test]
+fn jetstream_pull_subscribe_fetch_with_handler_status() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+    js.publish("foo", b"lorem").unwrap();
+
+    let consumer = js
+        .pull_subscribe_with_options(
+            "foo",
+            &PullSubscribeOptions::new().consumer_config(ConsumerConfig {
+                durable_name: Some("CONSUMER".to_string()),
+                ack_policy: AckPolicy::Explicit,
+                max_batch: 1,
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+
+    // Requests exceeding the max batch of the consumer are rejected with a 409 status.
+    let err = consumer.fetch_with_handler(10, |_| Ok(())).unwrap_err();
+    assert!(matches!(
+        PullStatus::from_io_error(&err),
+        Some(PullStatus::Conflict(_))
+    ));
+
+    let mut handled = 0;
+    consumer
+        .fetch_with_handler(1, |_| {
+            handled += 1;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(handled, 1);
+}
+
+#[test]
+fn jetstream_pull_subscribe_messages() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let consumer = js.pull_subscribe("foo").unwrap();
+
+    std::thread::spawn({
+        let js = js.clone();
+        move || {
+            for i in 0..50 {
+                js.publish("foo", format!("{}", i)).unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    });
+
+    let err = consumer
+        .messages_with_options(MessagesOptions {
+            batch: 0,
+            ..Default::default()
+        })
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let messages = consumer
+        .messages_with_options(MessagesOptions {
+            batch: 10,
+            expires: Duration::from_millis(200),
+            idle_heartbeat: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .unwrap();
+
+    for (i, message) in messages.take(50).enumerate() {
+        let message = message.unwrap();
+        assert_eq!(message.data, format!("{}", i).as_bytes());
+        message.ack().unwrap();
+    }
+}
+
+#[test]
+fn jetstream_ordered_consumer_recreated() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    for i in 0..10 {
+        js.publish("foo", format!("{}", i)).unwrap();
+    }
+
+    let subscription = js
+        .subscribe_with_options(
+            "foo",
+            &SubscribeOptions::ordered().idle_heartbeat(Duration::from_millis(100)),
+        )
+        .unwrap();
+
+    for i in 0..10 {
+        let message = subscription.next_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(message.data, format!("{}", i).as_bytes());
+    }
+
+    // Removing the consumer stops the heartbeats, which should recreate it.
+    let consumer = subscription.consumer_info().unwrap().name;
+    js.delete_consumer("TEST", &consumer).unwrap();
+
+    for i in 10..20 {
+        js.publish("foo", format!("{}", i)).unwrap();
+    }
+
+    for i in 10..20 {
+        let message = subscription.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.data, format!("{}", i).as_bytes());
+    }
+
+    assert_ne!(subscription.consumer_info().unwrap().name, consumer);
+}
+
+#[test]
+fn jetstream_nak_with_delay_and_ack_sync() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let info = js
+        .add_consumer(
+            "TEST",
+            ConsumerConfig {
+                durable_name: Some("CONSUMER".to_string()),
+                ack_policy: AckPolicy::Explicit,
+                max_deliver: 5,
+                backoff: vec![Duration::from_secs(1), Duration::from_secs(2)],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        info.config.backoff,
+        vec![Duration::from_secs(1), Duration::from_secs(2)]
+    );
+
+    js.publish("foo", b"data").unwrap();
+
+    let consumer = js
+        .pull_subscribe_with_options(
+            "foo",
+            &PullSubscribeOptions::new().durable_name("CONSUMER".to_string()),
+        )
+        .unwrap();
+
+    let message = consumer.fetch(1).unwrap().next().unwrap();
+    message.nak_with_delay(Duration::from_millis(500)).unwrap();
+
+    // The message is not redelivered before the delay passed.
+    let mut batch = consumer
+        .fetch(BatchOptions {
+            batch: 1,
+            no_wait: true,
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(batch.next().is_none());
+
+    std::thread::sleep(Duration::from_millis(700));
+
+    let message = consumer.fetch(1).unwrap().next().unwrap();
+    assert_eq!(message.data, b"data");
+    message.ack_sync().unwrap();
+
+    let info = js.consumer_info("TEST", "CONSUMER").unwrap();
+    assert_eq!(info.num_ack_pending, 0);
+}
+
+#[test]
+fn jetstream_auto_progress() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    js.add_consumer(
+        "TEST",
+        ConsumerConfig {
+            durable_name: Some("CONSUMER".to_string()),
+            ack_policy: AckPolicy::Explicit,
+            ack_wait: Duration::from_secs(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    js.publish("foo", b"first").unwrap();
+    js.publish("foo", b"second").unwrap();
+
+    let consumer = js
+        .pull_subscribe_with_options(
+            "foo",
+            &PullSubscribeOptions::new()
+                .durable_name("CONSUMER".to_string())
+                .auto_progress(Duration::from_millis(300)),
+        )
+        .unwrap();
+
+    let message = consumer.fetch(1).unwrap().next().unwrap();
+    let progress = message.auto_progress(Duration::from_millis(300));
+    std::thread::sleep(Duration::from_millis(1500));
+    progress.stop();
+    message.ack().unwrap();
+
+    consumer
+        .fetch_with_handler(1, |message| {
+            assert_eq!(message.data, b"second");
+            std::thread::sleep(Duration::from_millis(1500));
+            Ok(())
+        })
+        .unwrap();
+
+    let info = js.consumer_info("TEST", "CONSUMER").unwrap();
+    assert_eq!(info.num_redelivered, 0);
+    assert_eq!(info.num_ack_pending, 0);
+}
+
+#[test]
+fn jetstream_advisories() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    let advisories = js.advisories().unwrap();
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    match advisories.next_timeout(Duration::from_secs(5)).unwrap() {
+        Advisory::StreamAction(advisory) => {
+            assert_eq!(advisory.stream, "TEST");
+            assert_eq!(advisory.action, ActionKind::Create);
+        }
+        other => panic!("unexpected advisory: {:?}", other),
+    }
+}
+
+#[test]
+fn jetstream_update_consumer() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string(), "bar".to_string(), "baz".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let info = js
+        .add_consumer(
+            "TEST",
+            ConsumerConfig {
+                durable_name: Some("CONSUMER".to_string()),
+                filter_subjects: vec!["foo".to_string(), "bar".to_string()],
+                mem_storage: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(info.config.filter_subjects, vec!["foo", "bar"]);
+    assert!(info.config.mem_storage);
+    assert!(!info.cluster.leader.is_empty());
+
+    let mut config = info.config;
+    config.description = Some("updated".to_string());
+    config
+        .metadata
+        .insert("owner".to_string(), "tests".to_string());
+    let info = js.update_consumer("TEST", &config).unwrap();
+    assert_eq!(info.config.description.as_deref(), Some("updated"));
+    assert_eq!(info.config.metadata.get("owner").unwrap(), "tests");
+
+    let info = js.consumer_info("TEST", "CONSUMER").unwrap();
+    assert_eq!(info.config.description.as_deref(), Some("updated"));
+
+    js.update_consumer(
+        "TEST",
+        ConsumerConfig {
+            durable_name: None,
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn jetstream_account_limits() {
+    let s = util::run_server("tests/configs/jetstream_account_limits.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    let info = js.account_info().unwrap();
+    assert!(info.tiers.is_empty() || info.tiers.contains_key("R1"));
+    let tier = info.tier(1).unwrap();
+    assert_eq!(tier.limits.max_streams, 2);
+    assert!(tier.limits.max_bytes_required);
+
+    let mut config = StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    };
+    info.check_stream(&config).unwrap_err();
+
+    config.max_bytes = 2 * 1024 * 1024;
+    info.check_stream(&config).unwrap_err();
+
+    config.max_bytes = 512 * 1024;
+    info.check_stream(&config).unwrap();
+    js.add_stream(&config).unwrap();
+
+    let info = js.account_info().unwrap();
+    config.name = "OTHER".to_string();
+    config.subjects = vec!["bar".to_string()];
+    info.check_stream(&config).unwrap();
+    config.max_bytes = 768 * 1024;
+    info.check_stream(&config).unwrap_err();
+}
+
+#[test]
+fn jetstream_list_by_subject() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "ORDERS".to_string(),
+        subjects: vec!["orders.>".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+    js.add_stream(&StreamConfig {
+        name: "EVENTS".to_string(),
+        subjects: vec!["events.*".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let names = js
+        .stream_names_by_subject("orders.created")
+        .collect::<io::Result<Vec<String>>>()
+        .unwrap();
+    assert_eq!(names, vec!["ORDERS"]);
+
+    let streams = js
+        .list_streams_by_subject("events.>")
+        .collect::<io::Result<Vec<StreamInfo>>>()
+        .unwrap();
+    assert_eq!(streams.len(), 1);
+    assert_eq!(streams[0].config.name, "EVENTS");
+
+    assert_eq!(js.stream_names_by_subject("other").count(), 0);
+    assert_eq!(js.stream_names().count(), 2);
+
+    for name in ["A", "B"] {
+        js.add_consumer(
+            "ORDERS",
+            ConsumerConfig {
+                durable_name: Some(name.to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    }
+
+    let mut names = js
+        .consumer_names("ORDERS")
+        .unwrap()
+        .collect::<io::Result<Vec<String>>>()
+        .unwrap();
+    names.sort();
+    assert_eq!(names, vec!["A", "B"]);
+    assert_eq!(js.consumer_names("EVENTS").unwrap().count(), 0);
+}
+
+#[test]
+fn jetstream_purge_stream_with_options() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string(), "bar".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    for _ in 0..5 {
+        js.publish("foo", b"foo").unwrap();
+        js.publish("bar", b"bar").unwrap();
+    }
+
+    let response = js
+        .purge_stream_with_options(
+            "TEST",
+            &PurgeRequest {
+                filter: Some("foo".to_string()),
+                keep: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(response.purged, 3);
+
+    let response = js
+        .purge_stream_with_options(
+            "TEST",
+            &PurgeRequest {
+                filter: Some("bar".to_string()),
+                sequence: Some(7),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(response.purged, 3);
+    assert_eq!(js.stream_info("TEST").unwrap().state.messages, 4);
+
+    js.purge_stream_with_options(
+        "TEST",
+        &PurgeRequest {
+            sequence: Some(1),
+            keep: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn jetstream_cluster_administration() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream("ADMIN").unwrap();
+    js.add_consumer("ADMIN", "durable").unwrap();
+
+    let err = js.stream_leader_stepdown("").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = js.consumer_leader_stepdown("ADMIN", "").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = js.stream_remove_peer("ADMIN", "").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // A server which is not clustered rejects the requests.
+    js.stream_leader_stepdown("ADMIN").unwrap_err();
+    js.consumer_leader_stepdown("ADMIN", "durable").unwrap_err();
+    js.stream_remove_peer("ADMIN", "peer").unwrap_err();
+}
+
+#[test]
+fn jetstream_publish_async_timeout() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = JetStream::new(
+        nc.clone(),
+        JetStreamOptions::new()
+            .publish_async_max_pending(1)
+            .publish_async_timeout(Duration::from_millis(500)),
+    );
+
+    // A subscriber which never acknowledges, so acks are lost.
+    let _sub = nc.subscribe("lost").unwrap();
+
+    let ack = js.publish_async("lost", "1").unwrap();
+    let err = ack.wait().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    // Expired acks free up the pending window.
+    assert_eq!(js.publish_async_pending(), 0);
+    js.publish_async("lost", "2").unwrap();
+
+    let err = js.publish_async_complete().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(js.publish_async_pending(), 0);
+}
+
+#[test]
+fn jetstream_pause_consumer() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "PAUSE".to_string(),
+        subjects: vec!["pause".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+    js.add_consumer(
+        "PAUSE",
+        ConsumerConfig {
+            durable_name: Some("CONSUMER".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let pause_until = DateTime::now_utc() + Duration::from_secs(60);
+    let response = js.pause_consumer("PAUSE", "CONSUMER", pause_until).unwrap();
+    assert!(response.paused);
+    assert!(response.pause_until.is_some());
+    assert!(response.pause_remaining > Duration::ZERO);
+
+    let info = js.consumer_info("PAUSE", "CONSUMER").unwrap();
+    assert!(info.paused);
+
+    let response = js.resume_consumer("PAUSE", "CONSUMER").unwrap();
+    assert!(!response.paused);
+    assert_eq!(response.pause_remaining, Duration::ZERO);
+
+    let info = js.consumer_info("PAUSE", "CONSUMER").unwrap();
+    assert!(!info.paused);
+}
+
+#[test]
+fn jetstream_cluster_leader_stepdown() {
+    let servers = [
+        util::run_server("tests/configs/jetstream_cluster_1.conf"),
+        util::run_server("tests/configs/jetstream_cluster_2.conf"),
+        util::run_server("tests/configs/jetstream_cluster_3.conf"),
+    ];
+    let nc = nats::connect(&servers[0].client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    // The cluster needs to elect a meta leader before streams can be created.
+    wait_until(|| {
+        js.add_stream(&StreamConfig {
+            name: "CLUSTER".to_string(),
+            num_replicas: 3,
+            ..Default::default()
+        })
+        .is_ok()
+    });
+    js.add_consumer("CLUSTER", "durable").unwrap();
+
+    let is_current = |cluster: &ClusterInfo| {
+        !cluster.leader.is_empty()
+            && cluster.replicas.len() == 2
+            && cluster.replicas.iter().all(|replica| replica.current)
+    };
+    wait_until(|| is_current(&js.stream_info("CLUSTER").unwrap().cluster));
+    wait_until(|| is_current(&js.consumer_info("CLUSTER", "durable").unwrap().cluster));
+
+    // Stepping down returns once another leader was elected.
+    let previous = js.stream_info("CLUSTER").unwrap().cluster.leader;
+    let cluster = js.stream_leader_stepdown("CLUSTER").unwrap();
+    assert!(!cluster.leader.is_empty());
+    assert_ne!(cluster.leader, previous);
+
+    let previous = js
+        .consumer_info("CLUSTER", "durable")
+        .unwrap()
+        .cluster
+        .leader;
+    let cluster = js.consumer_leader_stepdown("CLUSTER", "durable").unwrap();
+    assert!(!cluster.leader.is_empty());
+    assert_ne!(cluster.leader, previous);
+
+    // Removing a peer returns once it is no longer a member.
+    wait_until(|| is_current(&js.stream_info("CLUSTER").unwrap().cluster));
+    let peer = js.stream_info("CLUSTER").unwrap().cluster.replicas[0]
+        .name
+        .clone();
+    let cluster = js.stream_remove_peer("CLUSTER", &peer).unwrap();
+    assert!(!cluster.leader.is_empty());
+    assert_ne!(cluster.leader, peer);
+    assert!(cluster.replicas.iter().all(|replica| replica.name != peer));
+}
+
+#[test]
+fn jetstream_pull_subscribe_ordered() {
+    let s = util::run_server("tests/configs/jetstream.conf");
+    let nc = nats::connect(&s.client_url()).unwrap();
+    let js = nats::jetstream::new(nc);
+
+    js.add_stream(&StreamConfig {
+        name: "TEST".to_string(),
+        subjects: vec!["foo".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    for i in 0..10 {
+        js.publish("foo", format!("{}", i)).unwrap();
+    }
+
+    js.pull_subscribe_with_options(
+        "foo",
+        &PullSubscribeOptions::new()
+            .durable_name("durable".to_string())
+            .ordered(),
+    )
+    .unwrap_err();
+
+    let consumer = js
+        .pull_subscribe_with_options("foo", &PullSubscribeOptions::new().ordered())
+        .unwrap();
+    let mut messages = consumer
+        .messages_with_options(MessagesOptions {
+            batch: 5,
+            expires: Duration::from_millis(500),
+            idle_heartbeat: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let mut name = String::new();
+    for i in 0..10 {
+        let message = messages.next().unwrap().unwrap();
+        assert_eq!(message.data, format!("{}", i).as_bytes());
+        name = message
+            .jetstream_message_info()
+            .unwrap()
+            .consumer
+            .to_string();
+    }
+
+    // Removing the consumer should recreate it right after the last delivered message.
+    js.delete_consumer("TEST", &name).unwrap();
+
+    for i in 10..20 {
+        js.publish("foo", format!("{}", i)).unwrap();
+    }
+
+    for i in 10..20 {
+        let message = messages.next().unwrap().unwrap();
+        assert_eq!(message.data, format!("{}", i).as_bytes());
+        assert_ne!(message.jetstream_message_info().unwrap().consumer, name);
+    }
+}
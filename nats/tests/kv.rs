@@ -339,3 +339,767 @@ fn key_value_keys() {
     assert!(keys.iter().any(|s| s == "baz"));
     assert_eq!(keys.len(), 1);
 }
+
+#[test]
+fn key_value_purge_deletes() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let bucket = context
+        .create_key_value(&Config {
+            bucket: "FOO".to_string(),
+            history: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+    bucket.put("foo", "1").unwrap();
+    bucket.put("foo", "2").unwrap();
+    bucket.delete("foo").unwrap();
+    bucket.put("bar", "1").unwrap();
+    bucket.purge("baz").unwrap();
+
+    // Recent markers are kept, but the revisions preceding them are removed.
+    bucket
+        .purge_deletes(std::time::Duration::from_secs(3600))
+        .unwrap();
+    assert_eq!(bucket.history("foo").unwrap().count(), 1);
+    assert_eq!(bucket.status().unwrap().values(), 3);
+
+    bucket.purge_deletes(std::time::Duration::ZERO).unwrap();
+    assert_eq!(bucket.status().unwrap().values(), 1);
+    assert_eq!(bucket.get("bar").unwrap(), Some(b"1".to_vec()));
+}
+
+#[test]
+fn key_value_watch_with_options() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let bucket = context
+        .create_key_value(&Config {
+            bucket: "WATCH".to_string(),
+            history: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let timeout = std::time::Duration::from_secs(5);
+
+    // Empty bucket, the initial values are done right away.
+    let mut watcher = bucket.watch_with_options(">", WatchOptions::new()).unwrap();
+    assert!(matches!(
+        watcher.next_timeout(timeout).unwrap(),
+        WatchEvent::InitialValuesDone
+    ));
+
+    bucket.put("foo", "1").unwrap();
+    bucket.put("foo", "2").unwrap();
+    bucket.put("bar", "1").unwrap();
+    bucket.delete("bar").unwrap();
+
+    let events = |watcher: &mut Watcher| {
+        let mut entries = Vec::new();
+        loop {
+            match watcher.next_timeout(timeout).unwrap() {
+                WatchEvent::Entry(entry) => entries.push(entry),
+                WatchEvent::InitialValuesDone => return entries,
+            }
+        }
+    };
+
+    let mut watcher = bucket.watch_with_options(">", WatchOptions::new()).unwrap();
+    let entries = events(&mut watcher);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].operation, Operation::Delete);
+
+    let mut watcher = bucket
+        .watch_with_options(">", WatchOptions::new().include_history())
+        .unwrap();
+    assert_eq!(events(&mut watcher).len(), 4);
+
+    let mut watcher = bucket
+        .watch_with_options(">", WatchOptions::new().ignore_deletes().meta_only())
+        .unwrap();
+    let entries = events(&mut watcher);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].key, "foo");
+    assert!(entries[0].value.is_empty());
+
+    let mut watcher = bucket
+        .watch_with_options("foo", WatchOptions::new().updates_only())
+        .unwrap();
+    assert!(events(&mut watcher).is_empty());
+    bucket.put("foo", "3").unwrap();
+    match watcher.next_timeout(timeout).unwrap() {
+        WatchEvent::Entry(entry) => assert_eq!(entry.value, b"3"),
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    assert!(bucket
+        .watch_with_options(">", WatchOptions::new().updates_only().include_history())
+        .is_err());
+}
+
+#[test]
+fn key_value_watch_from_revision() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let bucket = context
+        .create_key_value(&Config {
+            bucket: "RESUME".to_string(),
+            history: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let timeout = std::time::Duration::from_secs(5);
+
+    bucket.put("foo", "1").unwrap();
+    bucket.put("bar", "1").unwrap();
+    bucket.put("foo", "2").unwrap();
+    bucket.put("baz", "1").unwrap();
+
+    let mut watcher = bucket.watch_from_revision(">", 2).unwrap();
+    for revision in [3, 4] {
+        match watcher.next_timeout(timeout).unwrap() {
+            WatchEvent::Entry(entry) => assert_eq!(entry.revision, revision),
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+    assert!(matches!(
+        watcher.next_timeout(timeout).unwrap(),
+        WatchEvent::InitialValuesDone
+    ));
+
+    bucket.put("bar", "2").unwrap();
+    match watcher.next_timeout(timeout).unwrap() {
+        WatchEvent::Entry(entry) => assert_eq!(entry.revision, 5),
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    // Caught up already.
+    let mut watcher = bucket.watch_from_revision(">", 5).unwrap();
+    assert!(matches!(
+        watcher.next_timeout(timeout).unwrap(),
+        WatchEvent::InitialValuesDone
+    ));
+
+    // Drop the oldest revisions, as if they aged out.
+    context
+        .purge_stream_with_options(
+            "KV_RESUME",
+            &nats::jetstream::PurgeRequest {
+                sequence: Some(4),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let err = bucket.watch_from_revision(">", 1).err().unwrap();
+    let gap = RevisionGap::from_io_error(&err).unwrap();
+    assert_eq!(gap.revision, 1);
+    assert_eq!(gap.first_revision, 4);
+
+    assert!(bucket.watch_from_revision(">", 3).is_ok());
+}
+
+#[test]
+fn key_value_watch_from_revision_history_gap() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let bucket = context
+        .create_key_value(&Config {
+            bucket: "RESUME_HISTORY".to_string(),
+            history: 1,
+            ..Default::default()
+        })
+        .unwrap();
+
+    // The untouched key keeps the first revision of the stream retained.
+    bucket.put("bar", "1").unwrap();
+    bucket.put("foo", "1").unwrap();
+    bucket.put("foo", "2").unwrap();
+    bucket.put("foo", "3").unwrap();
+
+    let err = bucket.watch_from_revision("foo", 2).err().unwrap();
+    let gap = RevisionGap::from_io_error(&err).unwrap();
+    assert_eq!(gap.revision, 2);
+    assert_eq!(gap.first_revision, 4);
+
+    // Nothing is missing when resuming right before the retained revision.
+    let mut watcher = bucket.watch_from_revision("foo", 3).unwrap();
+    match watcher
+        .next_timeout(std::time::Duration::from_secs(5))
+        .unwrap()
+    {
+        WatchEvent::Entry(entry) => assert_eq!(entry.revision, 4),
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    assert!(bucket.watch_from_revision("bar", 1).is_ok());
+}
+
+#[test]
+fn key_value_keys_with_filter() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let bucket = context
+        .create_key_value(&Config {
+            bucket: "KEYS".to_string(),
+            history: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+    assert_eq!(bucket.keys().unwrap().count(), 0);
+
+    bucket.put("users.1.profile", "a").unwrap();
+    bucket.put("users.1.settings", "b").unwrap();
+    bucket.put("users.2.profile", "c").unwrap();
+    bucket.put("users.2.profile", "d").unwrap();
+    bucket.put("users.3.profile", "e").unwrap();
+    bucket.delete("users.3.profile").unwrap();
+    bucket.put("groups.1.profile", "f").unwrap();
+
+    let mut keys = bucket
+        .keys_with_filter("users.*.profile")
+        .unwrap()
+        .collect::<Vec<String>>();
+    keys.sort();
+    assert_eq!(keys, vec!["users.1.profile", "users.2.profile"]);
+
+    assert_eq!(bucket.keys_with_filter("users.>").unwrap().count(), 3);
+    assert_eq!(bucket.keys_with_filter("other.*").unwrap().count(), 0);
+    assert_eq!(bucket.keys().unwrap().count(), 4);
+
+    assert!(bucket.keys_with_filter("users.>.profile").is_err());
+    assert!(bucket.keys_with_filter("users..profile").is_err());
+
+    let mut keys = bucket
+        .keys_with_options(
+            &KeysOptions::new()
+                .filter("users.*.profile")
+                .include_deleted(),
+        )
+        .unwrap()
+        .collect::<Vec<String>>();
+    keys.sort();
+    assert_eq!(
+        keys,
+        vec!["users.1.profile", "users.2.profile", "users.3.profile"]
+    );
+    assert_eq!(
+        bucket
+            .keys_with_options(&KeysOptions::new().include_deleted())
+            .unwrap()
+            .count(),
+        5
+    );
+}
+
+#[test]
+fn key_value_delete_and_purge_if_revision() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let bucket = context
+        .create_key_value(&Config {
+            bucket: "CAS".to_string(),
+            history: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let revision = bucket.put("foo", "1").unwrap();
+    bucket.put("foo", "2").unwrap();
+
+    let err = bucket.delete_if_revision("foo", revision).unwrap_err();
+    assert!(nats::jetstream::Error::from_io_error(&err)
+        .unwrap()
+        .is_wrong_last_sequence());
+    assert_eq!(bucket.get("foo").unwrap(), Some(b"2".to_vec()));
+
+    let revision = bucket.entry("foo").unwrap().unwrap().revision;
+    bucket.delete_if_revision("foo", revision).unwrap();
+    let entry = bucket.entry("foo").unwrap().unwrap();
+    assert_eq!(entry.operation, Operation::Delete);
+
+    let err = bucket.purge_if_revision("foo", revision).unwrap_err();
+    assert!(nats::jetstream::Error::from_io_error(&err)
+        .unwrap()
+        .is_wrong_last_sequence());
+
+    bucket.purge_if_revision("foo", entry.revision).unwrap();
+    assert_eq!(bucket.history("foo").unwrap().count(), 1);
+    assert_eq!(
+        bucket.entry("foo").unwrap().unwrap().operation,
+        Operation::Purge
+    );
+}
+
+#[test]
+fn key_value_typed_store() {
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Profile {
+        name: String,
+        age: u8,
+    }
+
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let store = context
+        .create_key_value(&Config {
+            bucket: "TYPED".to_string(),
+            history: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let profiles = TypedStore::<Profile>::new(store.clone());
+    let alice = Profile {
+        name: "alice".to_string(),
+        age: 30,
+    };
+
+    let revision = profiles.put("alice", &alice).unwrap();
+    assert_eq!(profiles.get("alice").unwrap(), Some(alice));
+    assert_eq!(
+        store.get("alice").unwrap().unwrap(),
+        br#"{"name":"alice","age":30}"#
+    );
+
+    profiles
+        .update(
+            "alice",
+            &Profile {
+                name: "alice".to_string(),
+                age: 31,
+            },
+            revision,
+        )
+        .unwrap();
+    profiles.delete("alice").unwrap();
+    assert_eq!(profiles.get("alice").unwrap(), None);
+
+    let history = profiles
+        .history("alice")
+        .unwrap()
+        .collect::<std::io::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[1].value.as_ref().unwrap().age, 31);
+    assert!(history[2].value.is_none());
+
+    let revision = store.put("bob", b"not json").unwrap();
+    let err = profiles.entry("bob").unwrap_err();
+    let decode_error = DecodeError::from_io_error(&err).unwrap();
+    assert_eq!(decode_error.key, "bob");
+    assert_eq!(decode_error.revision, revision);
+
+    let mut watch = profiles.watch("bob").unwrap();
+    assert!(watch.next().unwrap().is_err());
+
+    let raw = TypedStore::<String, Raw>::with_codec(store.clone(), Raw);
+    assert_eq!(raw.get("bob").unwrap().unwrap(), "not json");
+
+    #[cfg(feature = "cbor")]
+    {
+        let cbor = TypedStore::with_codec(store.clone(), Cbor);
+        cbor.put("carol", &vec![1u32, 2, 3]).unwrap();
+        assert_eq!(cbor.get("carol").unwrap(), Some(vec![1u32, 2, 3]));
+    }
+
+    #[cfg(feature = "msgpack")]
+    {
+        let msgpack = TypedStore::with_codec(store, MessagePack);
+        let dave = Profile {
+            name: "dave".to_string(),
+            age: 40,
+        };
+        msgpack.put("dave", &dave).unwrap();
+        assert_eq!(msgpack.get("dave").unwrap(), Some(dave));
+    }
+}
+
+#[test]
+fn key_value_buckets_and_update() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    for bucket in ["A", "B"] {
+        context
+            .create_key_value(&Config {
+                bucket: bucket.to_string(),
+                storage: nats::jetstream::StorageType::Memory,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    context
+        .add_stream(&StreamConfig {
+            name: "OTHER".to_string(),
+            subjects: vec!["other".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+    let store = context.key_value("A").unwrap();
+    store.put("foo", "bar").unwrap();
+
+    let mut buckets = context
+        .key_value_buckets()
+        .collect::<std::io::Result<Vec<BucketStatus>>>()
+        .unwrap();
+    buckets.sort_by(|a, b| a.bucket().cmp(b.bucket()));
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0].bucket(), "A");
+    assert_eq!(buckets[0].values(), 1);
+    assert!(buckets[0].bytes() > 0);
+    assert_eq!(buckets[0].storage(), nats::jetstream::StorageType::Memory);
+    assert_eq!(buckets[0].replicas(), 1);
+    assert_eq!(buckets[1].bucket(), "B");
+
+    context
+        .update_key_value(&Config {
+            bucket: "A".to_string(),
+            history: 5,
+            max_age: std::time::Duration::from_secs(60),
+            max_bytes: 1024 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let status = store.status().unwrap();
+    assert_eq!(status.history(), 5);
+    assert_eq!(status.max_age(), std::time::Duration::from_secs(60));
+    assert_eq!(status.max_bytes(), 1024 * 1024);
+    assert_eq!(status.storage(), nats::jetstream::StorageType::Memory);
+    assert_eq!(store.get("foo").unwrap(), Some(b"bar".to_vec()));
+
+    assert!(context
+        .update_key_value(&Config {
+            bucket: "MISSING".to_string(),
+            ..Default::default()
+        })
+        .is_err());
+}
+
+#[test]
+fn key_value_mirror_and_sources() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let origin = context
+        .create_key_value(&Config {
+            bucket: "ORIGIN".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    origin.put("foo", "1").unwrap();
+
+    let mirror = context
+        .create_key_value(&Config {
+            bucket: "MIRROR".to_string(),
+            mirror: Some(nats::jetstream::Source {
+                name: "ORIGIN".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let info = context.stream_info("KV_MIRROR").unwrap();
+    assert!(info.config.subjects.is_empty());
+    assert_eq!(info.config.mirror.unwrap().name, "KV_ORIGIN");
+
+    // Writes through the mirror land in the origin bucket and are mirrored back.
+    mirror.put("bar", "2").unwrap();
+    assert_eq!(origin.get("bar").unwrap(), Some(b"2".to_vec()));
+
+    let wait_for = |store: &Store, key: &str, value: &[u8]| {
+        util::wait_until(|| store.get(key).unwrap().as_deref() == Some(value));
+    };
+    wait_for(&mirror, "foo", b"1");
+    wait_for(&mirror, "bar", b"2");
+
+    let mut keys = mirror.keys().unwrap().collect::<Vec<String>>();
+    keys.sort();
+    assert_eq!(keys, vec!["bar", "foo"]);
+
+    let aggregate = context
+        .create_key_value(&Config {
+            bucket: "AGGREGATE".to_string(),
+            sources: vec![nats::jetstream::Source {
+                name: "ORIGIN".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .unwrap();
+    wait_for(&aggregate, "foo", b"1");
+    aggregate.put("baz", "3").unwrap();
+    assert_eq!(origin.get("baz").unwrap(), None);
+
+    // A bucket is either a mirror or has sources.
+    let err = context
+        .create_key_value(&Config {
+            bucket: "BOTH".to_string(),
+            mirror: Some(nats::jetstream::Source {
+                name: "ORIGIN".to_string(),
+                ..Default::default()
+            }),
+            sources: vec![nats::jetstream::Source {
+                name: "AGGREGATE".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    // Sources can be changed through updates.
+    let other = context
+        .create_key_value(&Config {
+            bucket: "OTHER".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    other.put("qux", "4").unwrap();
+    context
+        .update_key_value(&Config {
+            bucket: "AGGREGATE".to_string(),
+            sources: vec![
+                nats::jetstream::Source {
+                    name: "ORIGIN".to_string(),
+                    ..Default::default()
+                },
+                nats::jetstream::Source {
+                    name: "OTHER".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+    wait_for(&aggregate, "qux", b"4");
+    assert_eq!(
+        context
+            .stream_info("KV_AGGREGATE")
+            .unwrap()
+            .config
+            .sources
+            .len(),
+        2
+    );
+
+    // Mirror buckets have no subjects but are listed too.
+    let mut buckets = context
+        .key_value_buckets()
+        .map(|status| status.unwrap().bucket().to_string())
+        .collect::<Vec<_>>();
+    buckets.sort();
+    assert_eq!(buckets, vec!["AGGREGATE", "MIRROR", "ORIGIN", "OTHER"]);
+}
+
+#[test]
+fn key_value_cached_store() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let store = context
+        .create_key_value(&Config {
+            bucket: "CACHED".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    store.put("config.a", "1").unwrap();
+    store.put("config.b", "2").unwrap();
+    store.put("other", "3").unwrap();
+
+    let cache = CachedStore::new(
+        store.clone(),
+        CacheOptions::new().key_filter("config.*").max_entries(2),
+    )
+    .unwrap();
+    cache.wait_ready(std::time::Duration::from_secs(5)).unwrap();
+
+    let status = cache.status();
+    assert!(status.ready);
+    assert!(status.watching);
+    assert_eq!(status.entries, 2);
+    assert_eq!(status.revision, 2);
+
+    assert_eq!(cache.get("config.a").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(cache.get("config.missing").unwrap(), None);
+    assert_eq!(cache.get("other").unwrap(), Some(b"3".to_vec()));
+
+    let wait_for = |key: &str, value: Option<&[u8]>| {
+        util::wait_until(|| cache.get(key).unwrap().as_deref() == value);
+    };
+
+    store.put("config.a", "4").unwrap();
+    wait_for("config.a", Some(b"4"));
+    store.delete("config.b").unwrap();
+    wait_for("config.b", None);
+
+    // Evicts the oldest entry, which is then read from the bucket again.
+    store.put("config.c", "5").unwrap();
+    wait_for("config.c", Some(b"5"));
+    assert_eq!(cache.status().entries, 2);
+    assert_eq!(cache.get("config.a").unwrap(), Some(b"4".to_vec()));
+}
+
+#[test]
+fn key_value_lock_and_election() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let unbounded = context
+        .create_key_value(&Config {
+            bucket: "UNBOUNDED".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(Lock::new(&unbounded, "lock").is_err());
+
+    let store = context
+        .create_key_value(&Config {
+            bucket: "LOCKS".to_string(),
+            max_age: Duration::from_secs(3),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let first = Lock::with_owner(&store, "lock", "first").unwrap();
+    let second = Lock::with_owner(&store, "lock", "second").unwrap();
+
+    let guard = first.try_acquire().unwrap().unwrap();
+    assert!(guard.is_held());
+    assert!(second.try_acquire().unwrap().is_none());
+    assert_eq!(second.holder().unwrap(), Some("first".to_string()));
+
+    // Renewals keep the lock past the max age of the bucket.
+    std::thread::sleep(Duration::from_secs(4));
+    assert!(guard.is_held());
+    assert!(guard.revision() > 1);
+    assert!(second.acquire(Duration::from_millis(500)).is_err());
+
+    guard.release().unwrap();
+    let guard = second.acquire(Duration::from_secs(1)).unwrap();
+    assert_eq!(first.holder().unwrap(), Some("second".to_string()));
+
+    // Taking over the key is noticed by the holder.
+    store.put("lock", "intruder").unwrap();
+    util::wait_until(|| !guard.is_held());
+    drop(guard);
+    store.delete("lock").unwrap();
+
+    let elected = Arc::new(AtomicUsize::new(0));
+    let lost = Arc::new(AtomicUsize::new(0));
+    let campaign = |owner: &str| {
+        let elected = elected.clone();
+        let lost = lost.clone();
+        LeaderElection::new(Lock::with_owner(&store, "leader", owner).unwrap())
+            .on_elected(move || {
+                elected.fetch_add(1, Ordering::SeqCst);
+            })
+            .on_lost(move || {
+                lost.fetch_add(1, Ordering::SeqCst);
+            })
+            .campaign()
+            .unwrap()
+    };
+
+    let leader = campaign("first");
+    util::wait_until(|| leader.is_leader());
+    let follower = campaign("second");
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(!follower.is_leader());
+    assert_eq!(elected.load(Ordering::SeqCst), 1);
+
+    leader.resign();
+    assert_eq!(lost.load(Ordering::SeqCst), 1);
+    util::wait_until(|| follower.is_leader());
+    assert_eq!(elected.load(Ordering::SeqCst), 2);
+
+    follower.resign();
+    assert_eq!(lost.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn key_value_update_with_and_increment() {
+    let server = util::run_server("tests/configs/jetstream.conf");
+    let client = nats::connect(&server.client_url()).unwrap();
+    let context = nats::jetstream::new(client);
+
+    let store = context
+        .create_key_value(&Config {
+            bucket: "COUNTERS".to_string(),
+            history: 5,
+            ..Default::default()
+        })
+        .unwrap();
+
+    store
+        .update_with("list", |value| {
+            assert_eq!(value, None);
+            b"a".to_vec()
+        })
+        .unwrap();
+    store
+        .update_with("list", |value| {
+            let mut value = value.unwrap().to_vec();
+            value.extend_from_slice(b",b");
+            value
+        })
+        .unwrap();
+    assert_eq!(store.get("list").unwrap(), Some(b"a,b".to_vec()));
+
+    assert_eq!(store.increment("count", 5).unwrap(), 5);
+    assert_eq!(store.increment("count", -2).unwrap(), 3);
+    store.delete("count").unwrap();
+    assert_eq!(store.increment("count", 1).unwrap(), 1);
+
+    store.put("text", "abc").unwrap();
+    assert_eq!(
+        store.increment("text", 1).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+
+    // Concurrent increments are all applied.
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    store.increment("shared", 1).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(store.get("shared").unwrap(), Some(b"40".to_vec()));
+}
@@ -49,6 +49,7 @@ fn no_messages() {
             batch: 10,
             expires: None,
             no_wait: true,
+            ..Default::default()
         })
         .unwrap();
 
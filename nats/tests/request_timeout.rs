@@ -57,6 +57,7 @@ fn request_timeout() {
             expires: Some(Duration::from_millis(200).as_nanos() as usize),
             batch: 2,
             no_wait: false,
+            ..Default::default()
         })
         .unwrap();
 
@@ -158,3 +158,14 @@ pub fn run_basic_jetstream() -> (Server, Connection, JetStream) {
 
     (s, nc, js)
 }
+
+/// Polls the condition until it holds, panicking if it does not within five seconds.
+pub fn wait_until<F: FnMut() -> bool>(mut condition: F) {
+    for _ in 0..50 {
+        if condition() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("condition was not met in time");
+}